@@ -12,20 +12,22 @@ use std::time::Duration;
 use glutin_window::GlutinWindow as Window;
 use opengl_graphics::{GlGraphics, OpenGL, Texture, TextureSettings};
 use piston::event_loop::{EventSettings, Events};
-use piston::input::{RenderArgs, RenderEvent, UpdateArgs, UpdateEvent};
+use piston::input::{Button, Key, PressEvent, RenderArgs, RenderEvent, UpdateArgs, UpdateEvent};
 use piston::window::WindowSettings;
 use viewmodel::ViewModel;
-use interpreter::Interpreter;
+use interpreter::{Interpreter, SharedInterpreter};
+use core::budget::CancelHandle;
 use crate::viewmodel::{InterpreterViewModel, VisualObject};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use graphics::math::Matrix2d;
 use std::cell::RefCell;
-use std::sync::{Arc, RwLock};
+use utilities::geometry::{cubic_bezier, Vector2D};
 
+// Loads images from the assets folder by file name, once each.
 pub struct TextureLoader {
     assets: PathBuf,
-    textures: HashMap<PathBuf, Texture>,
+    textures: HashMap<String, Result<Texture, String>>,
 }
 
 impl TextureLoader {
@@ -35,18 +37,14 @@ impl TextureLoader {
             textures: HashMap::new(),
         }
     }
-    pub fn load_texture(&mut self, path: &PathBuf) -> Result<&Texture, String> {
-        let has = {
-            self.textures.get(path).is_some()
-        };
-        if has {
-            Ok(self.textures.get(path).unwrap())
-        } else {
+    // Failures are kept too, so a missing image is not read again every frame.
+    pub fn load_texture(&mut self, name: &str) -> Result<&Texture, String> {
+        if !self.textures.contains_key(name) {
             let texture = Texture::from_path(
-                self.assets.join(path), &TextureSettings::new())?;
-            self.textures.insert(path.clone(), texture);
-            Ok(self.textures.get(path).unwrap())
+                self.assets.join(name), &TextureSettings::new());
+            self.textures.insert(name.to_string(), texture);
         }
+        self.textures.get(name).unwrap().as_ref().map_err(|e| e.clone())
     }
 }
 
@@ -59,22 +57,60 @@ impl Render for VisualObject {
         use graphics::*;
         match self {
             VisualObject::ImageObject(o) => {
-                let texture = texture_loader.load_texture(&o.image).unwrap();
+                // a program can name an image that is not in the assets
+                let texture = match texture_loader.load_texture(&o.image) {
+                    Ok(texture) => texture,
+                    Err(_) => return,
+                };
                 let (w, h) = texture.get_size();
                 let transform = transform
                     .trans(o.x, o.y)
                     .rot_rad(o.rotation)
+                    .scale(o.scale, o.scale)
                     .trans(-(w as f64) / 2.0, -(h as f64) / 2.0);
                 image(texture, transform, gl);
             }
             VisualObject::Line(o) => {
-                line(
-                    [0.0, 0.0, 0.0, 1.0],
-                    1.0,
-                    [o.x1, o.y1, o.x2, o.y2],
-                    transform,
-                    gl
-                )
+                for segment in o.points.windows(2) {
+                    line(
+                        o.color,
+                        o.width / 2.0,
+                        [segment[0][0], segment[0][1], segment[1][0], segment[1][1]],
+                        transform,
+                        gl
+                    )
+                }
+            }
+            VisualObject::Polygon(o) => {
+                if let Some(fill) = o.fill {
+                    polygon(fill, &o.points, transform, gl);
+                }
+                for (i, p) in o.points.iter().enumerate() {
+                    let q = o.points[(i + 1) % o.points.len()];
+                    line(o.color, o.width / 2.0, [p[0], p[1], q[0], q[1]], transform, gl);
+                }
+            }
+            VisualObject::Background(o) => {
+                clear(o.color, gl);
+            }
+            VisualObject::Circle(o) => {
+                let rect = rectangle::centered_square(o.x, o.y, o.radius);
+                circle_arc(o.color, o.width / 2.0, 0.0, 2.0 * std::f64::consts::PI, rect, transform, gl);
+            }
+            VisualObject::Arc(o) => {
+                let rect = rectangle::centered_square(o.x, o.y, o.radius);
+                let (start, end) = if o.start < o.end { (o.start, o.end) } else { (o.end, o.start) };
+                circle_arc(o.color, o.width / 2.0, start, end, rect, transform, gl);
+            }
+            VisualObject::Bezier(o) => {
+                const SEGMENTS: usize = 32;
+                let controls = [0, 1, 2, 3].map(|i| Vector2D { x: o.points[i][0], y: o.points[i][1] });
+                let samples: Vec<Vector2D> = (0..=SEGMENTS)
+                    .map(|i| cubic_bezier(&controls, i as f64 / SEGMENTS as f64))
+                    .collect();
+                for pair in samples.windows(2) {
+                    line(o.color, o.width / 2.0, [pair[0].x, pair[0].y, pair[1].x, pair[1].y], transform, gl);
+                }
             }
         }
     }
@@ -85,13 +121,15 @@ pub struct App<VM: ViewModel> {
     view_model: VM,
     rotation: f64,  // Rotation for the square.
     texture_loader: TextureLoader,
+    cancel_handle: CancelHandle,
+    interpreter: SharedInterpreter,
 }
 
 impl<VM: ViewModel> App<VM> {
     fn render(&mut self, args: &RenderArgs) {
         use graphics::*;
 
-        const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+        const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
         const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
 
         let rotation = self.rotation;
@@ -101,8 +139,8 @@ impl<VM: ViewModel> App<VM> {
         let visual_objects = self.view_model.visual_objects();
         self.gl.draw(args.viewport(), |c, gl| {
 
-            // Clear the screen.
-            clear(GREEN, gl);
+            // Clear the screen, in case the program has no 画面.
+            clear(WHITE, gl);
 
             for vo in visual_objects.iter() {
                 let transform = c.transform.trans(x, y).scale(1.0, -1.0);
@@ -112,16 +150,32 @@ impl<VM: ViewModel> App<VM> {
     }
 
     fn update(&mut self, args: &UpdateArgs) {
+        // while a program runs, turtles wait where they are
+        if let Some(Err(e)) = self.interpreter.try_with(|interpreter| interpreter.tick(args.dt)) {
+            eprintln!("{:?}", e);
+        }
+    }
+
+    fn press(&mut self, button: &Button) {
+        if let Button::Keyboard(Key::Pause) = button {
+            self.cancel_handle.cancel();
+        }
     }
 }
 
-async fn repl(interpreter: Arc<RwLock<Interpreter>>) {
+async fn repl(interpreter: SharedInterpreter) {
     let mut s = String::new();
     let mut stream = BufReader::new(stdin());
     loop {
         stream.read_line(&mut s).await.unwrap();
-        interpreter.write().unwrap().exec(s.as_str());
-        s = String::new();
+        let program = std::mem::take(&mut s);
+        let interpreter = interpreter.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            interpreter.exec(program.as_str())
+        }).await.unwrap();
+        if let Err(e) = result {
+            eprintln!("{:?}", e);
+        }
     }
 }
 
@@ -135,6 +189,10 @@ async fn event_loop<VM: ViewModel>(mut app: App<VM>, mut events: Events, window:
             app.update(&args);
         }
 
+        if let Some(button) = e.press_args() {
+            app.press(&button);
+        }
+
         tokio::time::sleep(Duration::from_millis(10)).await;
     }
 }
@@ -143,7 +201,10 @@ async fn event_loop<VM: ViewModel>(mut app: App<VM>, mut events: Events, window:
 async fn main() {
     // Change this to OpenGL::V2_1 if not working.
     let opengl = OpenGL::V3_2;
-    let mut interpreter = Arc::new(RwLock::new(Interpreter::new()));
+    let mut interpreter = Interpreter::new();
+    interpreter.set_animated(true);
+    let interpreter = SharedInterpreter::new(interpreter);
+    let cancel_handle = interpreter.cancel_handle();
 
     let view_model = InterpreterViewModel::new(interpreter.clone());
 
@@ -165,6 +226,8 @@ async fn main() {
         rotation: 0.0,
         view_model,
         texture_loader,
+        cancel_handle,
+        interpreter: interpreter.clone(),
     };
 
     //repl(interpreter.clone()).await;
@@ -1,15 +1,19 @@
-use std::path::PathBuf;
-use interpreter::Interpreter;
+use interpreter::{Interpreter, SharedInterpreter};
 use std::rc::Rc;
-use std::sync::{Arc, RwLock};
 use core::object::Object;
 use std::cell::RefCell;
+use utilities::color::Rgb;
 
 
 #[derive(Clone, Debug)]
 pub enum VisualObject {
     ImageObject(ImageObjectImpl),
     Line(LineImpl),
+    Polygon(PolygonImpl),
+    Circle(CircleImpl),
+    Arc(ArcImpl),
+    Bezier(BezierImpl),
+    Background(BackgroundImpl),
 }
 
 #[derive(Clone, Debug)]
@@ -17,15 +21,56 @@ pub struct ImageObjectImpl {
     pub x: f64,
     pub y: f64,
     pub rotation: f64,
-    pub image: PathBuf,
+    pub image: String,
+    pub scale: f64,
 }
 
 #[derive(Clone, Debug)]
 pub struct LineImpl {
-    pub x1: f64,
-    pub y1: f64,
-    pub x2: f64,
-    pub y2: f64,
+    pub points: Vec<[f64; 2]>,
+    pub color: [f32; 4],
+    pub width: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PolygonImpl {
+    pub points: Vec<[f64; 2]>,
+    pub color: [f32; 4],
+    pub width: f64,
+    pub fill: Option<[f32; 4]>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CircleImpl {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    pub color: [f32; 4],
+    pub width: f64,
+}
+
+// start and end are in radians
+#[derive(Clone, Debug)]
+pub struct ArcImpl {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    pub start: f64,
+    pub end: f64,
+    pub color: [f32; 4],
+    pub width: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct BezierImpl {
+    pub points: [[f64; 2]; 4],
+    pub color: [f32; 4],
+    pub width: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct BackgroundImpl {
+    pub color: [f32; 4],
 }
 
 pub trait ViewModel {
@@ -33,47 +78,128 @@ pub trait ViewModel {
 }
 
 pub struct InterpreterViewModel {
-    model: Arc<RwLock<Interpreter>>,
+    model: SharedInterpreter,
+    // last frame, shown while a program holds the interpreter
+    last_visual_objects: RefCell<Vec<VisualObject>>,
 }
 
 impl ViewModel for InterpreterViewModel {
     fn visual_objects(&self) -> Vec<VisualObject> {
-        let model = self.model.read().unwrap();
-        let x = model.get_symbol("x");
-        let y = model.get_symbol("y");
-        let x1 = model.get_symbol("x1");
-        let y1= model.get_symbol("y1");
-        let x2 = model.get_symbol("x2");
-        let y2= model.get_symbol("y2");
-        let direction = model.get_symbol("direction");
-        let visible = model.get_symbol("visible");
-        let mut turtles: Vec<VisualObject> = model.get_objects().iter().filter_map(|obj| {
-            if obj.get_member(visible).ok()?.as_bool().ok()? {
-                Some(VisualObject::ImageObject(ImageObjectImpl {
-                    x: obj.get_member(x).ok()?.as_num().ok()?,
-                    y: obj.get_member(y).ok()?.as_num().ok()?,
-                    rotation: obj.get_member(direction).ok()?.as_num().ok()?.to_radians(),
-                    image: PathBuf::from("ayumi.png"),
-                }))
-            } else { None }
-        }).collect();
-        turtles.extend(model.get_objects().iter().filter_map(|obj| {
-            Some(VisualObject::Line(LineImpl {
-                x1: obj.get_member(x1).ok()?.as_num().ok()?,
-                y1: obj.get_member(y1).ok()?.as_num().ok()?,
-                x2: obj.get_member(x2).ok()?.as_num().ok()?,
-                y2: obj.get_member(y2).ok()?.as_num().ok()?,
-            }))
-        }));
-        turtles.reverse();
+        let turtles = match self.model.try_with(|model| visual_objects_of(model)) {
+            Some(turtles) => turtles,
+            None => return self.last_visual_objects.borrow().clone(),
+        };
+        *self.last_visual_objects.borrow_mut() = turtles.clone();
         turtles
     }
 }
 
+fn visual_objects_of(model: &Interpreter) -> Vec<VisualObject> {
+    let vm = model.vm();
+    let x = model.get_symbol("x");
+    let y = model.get_symbol("y");
+    let color = model.get_symbol("color");
+    let width = model.get_symbol("width");
+    let fill = model.get_symbol("fill");
+    let line_obj_id = model.get_object_id("線");
+    let shape_obj_id = model.get_object_id("図形");
+    let circle_obj_id = model.get_object_id("円");
+    let arc_obj_id = model.get_object_id("弧");
+    let curve_obj_id = model.get_object_id("曲線");
+    let num = |obj: &Object, name: &str| obj.get_member_str(name, vm).ok()?.as_num().ok();
+    let stroke_color = |obj: &Object| Some(Rgb::from_hex(obj.get_member(color, vm).ok()?.as_str().ok()?)?.to_rgba());
+    let direction = model.get_symbol("direction");
+    let visible = model.get_symbol("visible");
+    let image = model.get_symbol("image");
+    let scale = model.get_symbol("scale");
+    let turtle_obj_id = model.get_object_id("タートル");
+    let animation = vm.animation();
+    let mut turtles: Vec<VisualObject> = model.get_objects().iter().filter(|obj| obj.is_subclass(turtle_obj_id, vm)).filter_map(|obj| {
+        if obj.get_member(visible, vm).ok()?.as_bool().ok()? {
+            let (x, y) = animation.position(obj.id(), (
+                obj.get_member(x, vm).ok()?.as_num().ok()?,
+                obj.get_member(y, vm).ok()?.as_num().ok()?,
+            ));
+            Some(VisualObject::ImageObject(ImageObjectImpl {
+                x,
+                y,
+                rotation: animation.direction(obj.id(), obj.get_member(direction, vm).ok()?.as_num().ok()?).to_radians(),
+                image: obj.get_member(image, vm).ok()?.as_str().ok()?.to_string(),
+                scale: obj.get_member(scale, vm).ok()?.as_num().ok()?,
+            }))
+        } else { None }
+    }).collect();
+    turtles.extend(model.get_objects().iter().filter(|obj| obj.is_subclass(line_obj_id, vm)).filter_map(|obj| {
+        if !obj.get_member(visible, vm).ok()?.as_bool().ok()? {
+            return None;
+        }
+        let points = animation.line_points(obj.id(), core::object::line::points(obj, vm).ok()?);
+        if points.len() < 2 {
+            return None;
+        }
+        Some(VisualObject::Line(LineImpl {
+            points: points.into_iter().map(|(x, y)| [x, y]).collect(),
+            color: Rgb::from_hex(obj.get_member(color, vm).ok()?.as_str().ok()?)?.to_rgba(),
+            width: obj.get_member(width, vm).ok()?.as_num().ok()?,
+        }))
+    }));
+    turtles.extend(model.get_objects().iter().filter(|obj| obj.is_subclass(shape_obj_id, vm) && animation.is_drawn(obj.id())).filter_map(|obj| {
+        Some(VisualObject::Polygon(PolygonImpl {
+            points: core::object::shape::points(obj, vm).ok()?.into_iter().map(|(x, y)| [x, y]).collect(),
+            color: Rgb::from_hex(obj.get_member(color, vm).ok()?.as_str().ok()?)?.to_rgba(),
+            width: obj.get_member(width, vm).ok()?.as_num().ok()?,
+            fill: obj.get_member(fill, vm).ok()?.as_str().ok()
+                .and_then(Rgb::from_hex)
+                .map(|c| c.to_rgba()),
+        }))
+    }));
+    turtles.extend(model.get_objects().iter().filter(|obj| obj.is_subclass(circle_obj_id, vm) && animation.is_drawn(obj.id())).filter_map(|obj| {
+        Some(VisualObject::Circle(CircleImpl {
+            x: num(obj, "cx")?,
+            y: num(obj, "cy")?,
+            radius: num(obj, "r")?,
+            color: stroke_color(obj)?,
+            width: obj.get_member(width, vm).ok()?.as_num().ok()?,
+        }))
+    }));
+    turtles.extend(model.get_objects().iter().filter(|obj| obj.is_subclass(arc_obj_id, vm) && animation.is_drawn(obj.id())).filter_map(|obj| {
+        Some(VisualObject::Arc(ArcImpl {
+            x: num(obj, "cx")?,
+            y: num(obj, "cy")?,
+            radius: num(obj, "r")?,
+            start: num(obj, "start")?.to_radians(),
+            end: num(obj, "end")?.to_radians(),
+            color: stroke_color(obj)?,
+            width: obj.get_member(width, vm).ok()?.as_num().ok()?,
+        }))
+    }));
+    turtles.extend(model.get_objects().iter().filter(|obj| obj.is_subclass(curve_obj_id, vm) && animation.is_drawn(obj.id())).filter_map(|obj| {
+        Some(VisualObject::Bezier(BezierImpl {
+            points: [
+                [num(obj, "x1")?, num(obj, "y1")?],
+                [num(obj, "cx1")?, num(obj, "cy1")?],
+                [num(obj, "cx2")?, num(obj, "cy2")?],
+                [num(obj, "x2")?, num(obj, "y2")?],
+            ],
+            color: stroke_color(obj)?,
+            width: obj.get_member(width, vm).ok()?.as_num().ok()?,
+        }))
+    }));
+    // reversed below, so the background is drawn first
+    turtles.extend(vm.get_object_in_assigns_from_symbol("画面").ok().and_then(|screen| {
+        Some(VisualObject::Background(BackgroundImpl {
+            color: Rgb::from_hex(screen.get_member_str("background", vm).ok()?.as_str().ok()?)?.to_rgba(),
+        }))
+    }));
+    turtles.reverse();
+    turtles
+}
+
 impl InterpreterViewModel {
-    pub fn new(interpreter: Arc<RwLock<Interpreter>>) -> Self {
+    pub fn new(interpreter: SharedInterpreter) -> Self {
         Self {
             model: interpreter,
+            last_visual_objects: RefCell::new(vec![]),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,39 @@
+// Only `Interpreter::new` and `exec` are used, so the same file runs on the
+// VM from before the locks were dropped. To compare the two, run it there
+// with `cargo bench --bench dispatch -- --save-baseline before`, then here
+// with `-- --baseline before`.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use interpreter::Interpreter;
+
+// the 繰り返す around each call, to subtract from the cases below
+const LOOP_ONLY: &str = r#"かめた＝タートル！作る。
+「」！１００００ 繰り返す。
+"#;
+
+const NATIVE_METHOD: &str = r#"かめた＝タートル！作る。
+「かめた！０ 右回り。」！１００００ 繰り返す。
+"#;
+
+const BLOCK_METHOD: &str = r#"かめた＝タートル！作る。
+かめた：何もしない＝「｜ｎ｜ ｎ。」。
+「かめた！１ 何もしない。」！１００００ 繰り返す。
+"#;
+
+fn bench_program(c: &mut Criterion, name: &str, program: &str) {
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            Interpreter::new,
+            |mut interpreter| interpreter.exec(program),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn dispatch(c: &mut Criterion) {
+    bench_program(c, "dispatch/loop_only", LOOP_ONLY);
+    bench_program(c, "dispatch/native_method", NATIVE_METHOD);
+    bench_program(c, "dispatch/block_method", BLOCK_METHOD);
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use core::vm::Evaluator;
+use interpreter::Interpreter;
+
+const SPIRAL: &str = r#"かめた＝タートル！作る。
+長さ＝１。
+「かめた！（長さ） 歩く ９１ 右回り。 長さ＝長さ＋１。」！２０００ 繰り返す。
+"#;
+
+const COUNTER: &str = r#"てすと＝０。
+「「てすと＜５０００。」！ならば 「てすと＝てすと＋１。」 実行。」！１００００ 繰り返す。
+"#;
+
+fn bench_program(c: &mut Criterion, name: &str, program: &str) {
+    let mut group = c.benchmark_group(name);
+    for (label, evaluator) in vec![
+        ("tree_walker", Evaluator::TreeWalker),
+        ("bytecode", Evaluator::Bytecode),
+    ] {
+        group.bench_function(label, |b| {
+            b.iter_batched(
+                || {
+                    let mut interpreter = Interpreter::new();
+                    interpreter.set_evaluator(evaluator);
+                    interpreter
+                },
+                |mut interpreter| interpreter.exec(program),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn evaluator(c: &mut Criterion) {
+    bench_program(c, "spiral", SPIRAL);
+    bench_program(c, "counter", COUNTER);
+}
+
+criterion_group!(benches, evaluator);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use interpreter::Interpreter;
+
+const SPIRAL: &str = r#"かめた＝タートル！作る。
+長さ＝１。
+「かめた！（長さ） 歩く。 かめた！８９ 右回り。 長さ＝長さ＋１。」！５０００ 繰り返す。
+"#;
+
+// The same spiral drawn the way turtles did before trails, one 線 object per
+// step. The array keeps them from being collected.
+const SPIRAL_ONE_LINE_PER_STEP: &str = r#"かめた＝タートル！作る　ペンなし。
+線たち＝配列！作る。
+長さ＝１。
+「ひとつ＝線！作る。 ひとつ：x1＝かめた：x。 ひとつ：y1＝かめた：y。 かめた！（長さ） 歩く。 ひとつ：x2＝かめた：x。 ひとつ：y2＝かめた：y。 線たち！（ひとつ） 書く。 かめた！８９ 右回り。 長さ＝長さ＋１。」！５０００ 繰り返す。
+"#;
+
+// A spiral drawn while 衝突 is set, so every step runs a collision pass.
+// Another turtle's handler has to be checked against the growing trail; the
+// drawing turtle's own handler never meets its own trail.
+const SPIRAL_WATCHED_BY_OTHER: &str = r#"ねこ＝タートル！作る。
+ねこ！１０００ 歩く。
+ねこ：衝突＝「｜相手｜ 相手。」。
+かめた＝タートル！作る。
+長さ＝１。
+「かめた！（長さ） 歩く。 かめた！８９ 右回り。 長さ＝長さ＋１。」！５０００ 繰り返す。
+"#;
+
+const SPIRAL_WATCHED_BY_ITSELF: &str = r#"かめた＝タートル！作る。
+かめた：衝突＝「｜相手｜ 相手。」。
+長さ＝１。
+「かめた！（長さ） 歩く。 かめた！８９ 右回り。 長さ＝長さ＋１。」！５０００ 繰り返す。
+"#;
+
+// what a view model reads from every line each frame
+fn extract_lines(interpreter: &Interpreter) -> usize {
+    let vm = interpreter.vm();
+    let line = interpreter.get_object_id("線");
+    interpreter.get_objects().iter()
+        .filter(|obj| obj.is_subclass(line, vm))
+        .filter_map(|obj| {
+            obj.get_member_str("color", vm).ok()?;
+            obj.get_member_str("width", vm).ok()?;
+            Some(core::object::line::points(obj, vm).ok()?.len())
+        })
+        .sum()
+}
+
+fn bench_extract(c: &mut Criterion, name: &str, program: &str) {
+    let mut interpreter = Interpreter::new();
+    interpreter.exec(program);
+    println!("{}: {} objects on the heap, {} points",
+             name, interpreter.get_objects().len(), extract_lines(&interpreter));
+
+    c.bench_function(name, |b| {
+        b.iter(|| extract_lines(&interpreter))
+    });
+}
+
+fn bench_draw(c: &mut Criterion, name: &str, program: &str) {
+    c.bench_function(name, |b| {
+        b.iter_batched(
+            Interpreter::new,
+            |mut interpreter| interpreter.exec(program),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn trails(c: &mut Criterion) {
+    bench_extract(c, "trails/extract_one_line_per_step_5000", SPIRAL_ONE_LINE_PER_STEP);
+    bench_extract(c, "trails/extract_spiral_5000", SPIRAL);
+    bench_draw(c, "trails/draw_spiral_5000_watched_by_other", SPIRAL_WATCHED_BY_OTHER);
+    bench_draw(c, "trails/draw_spiral_5000_watched_by_itself", SPIRAL_WATCHED_BY_ITSELF);
+}
+
+criterion_group!(benches, trails);
+criterion_main!(benches);
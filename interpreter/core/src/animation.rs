@@ -0,0 +1,167 @@
+use crate::vm::ObjectId;
+use std::collections::VecDeque;
+
+/// Something a turtle did that front-ends can replay over time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Motion {
+    // `line` is the 線 the step extended, if the pen was down.
+    Move { turtle: ObjectId, from: (f64, f64), to: (f64, f64), line: Option<ObjectId> },
+    Turn { turtle: ObjectId, from: f64, to: f64 },
+    // a 円, 弧, 曲線 or 図形 that shows up once its time has passed
+    Draw { object: ObjectId },
+}
+
+/// Programs still run to the end at once. When enabled, the VM also queues
+/// every motion with its duration in seconds, and the host's `tick` plays
+/// them back one after another. Disabled, everything is shown as it is.
+#[derive(Default)]
+pub struct Animation {
+    enabled: bool,
+    motions: VecDeque<(Motion, f64)>,
+    elapsed: f64,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.finish();
+        }
+    }
+
+    pub fn push(&mut self, motion: Motion, duration: f64) {
+        if self.enabled {
+            self.motions.push_back((motion, duration.max(0.0)));
+        }
+    }
+
+    pub fn tick(&mut self, dt: f64) {
+        self.elapsed += dt;
+        while let Some((_, duration)) = self.motions.front() {
+            if self.elapsed < *duration {
+                return;
+            }
+            self.elapsed -= *duration;
+            self.motions.pop_front();
+        }
+        self.elapsed = 0.0;
+    }
+
+    // Skips to the end of everything queued.
+    pub fn finish(&mut self) {
+        self.motions.clear();
+        self.elapsed = 0.0;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.motions.is_empty()
+    }
+
+    // How far the motion being played has got, from 0 to 1.
+    fn progress(&self) -> f64 {
+        match self.motions.front() {
+            Some((_, duration)) if *duration > 0.0 => (self.elapsed / duration).min(1.0),
+            _ => 0.0,
+        }
+    }
+
+    fn pending(&self) -> impl Iterator<Item = (usize, &Motion)> {
+        self.motions.iter().map(|(motion, _)| motion).enumerate()
+    }
+
+    // Where the turtle is shown, given where the program left it.
+    pub fn position(&self, turtle: ObjectId, current: (f64, f64)) -> (f64, f64) {
+        self.pending()
+            .find_map(|(i, motion)| match motion {
+                Motion::Move { turtle: t, from, to, .. } if *t == turtle =>
+                    Some(if i == 0 { lerp2(*from, *to, self.progress()) } else { *from }),
+                _ => None,
+            })
+            .unwrap_or(current)
+    }
+
+    pub fn direction(&self, turtle: ObjectId, current: f64) -> f64 {
+        self.pending()
+            .find_map(|(i, motion)| match motion {
+                Motion::Turn { turtle: t, from, to } if *t == turtle =>
+                    Some(if i == 0 { lerp(*from, *to, self.progress()) } else { *from }),
+                _ => None,
+            })
+            .unwrap_or(current)
+    }
+
+    // The corners of a line drawn so far, ending part way along the step
+    // being played.
+    pub fn line_points(&self, line: ObjectId, mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+        let is_step = |motion: &Motion| matches!(motion, Motion::Move { line: Some(l), .. } if *l == line);
+        let waiting = self.pending().filter(|(_, motion)| is_step(motion)).count();
+        if waiting == 0 {
+            return points;
+        }
+        points.truncate(points.len().saturating_sub(waiting));
+        if let Some((Motion::Move { from, to, .. }, _)) = self.motions.front().filter(|(motion, _)| is_step(motion)) {
+            points.push(lerp2(*from, *to, self.progress()));
+        }
+        points
+    }
+
+    pub fn is_drawn(&self, object: ObjectId) -> bool {
+        !self.pending().any(|(_, motion)| *motion == Motion::Draw { object })
+    }
+}
+
+fn lerp(from: f64, to: f64, t: f64) -> f64 {
+    from + (to - from) * t
+}
+
+fn lerp2(from: (f64, f64), to: (f64, f64), t: f64) -> (f64, f64) {
+    (lerp(from.0, to.0, t), lerp(from.1, to.1, t))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation::{Animation, Motion};
+    use crate::vm::ObjectId;
+
+    #[test]
+    fn tick_plays_motions_in_order() {
+        let turtle = ObjectId(0);
+        let line = ObjectId(1);
+        let mut animation = Animation::new();
+        animation.set_enabled(true);
+        animation.push(Motion::Move { turtle, from: (0.0, 0.0), to: (100.0, 0.0), line: Some(line) }, 1.0);
+        animation.push(Motion::Turn { turtle, from: 0.0, to: 90.0 }, 1.0);
+        animation.push(Motion::Move { turtle, from: (100.0, 0.0), to: (100.0, 100.0), line: Some(line) }, 1.0);
+        let points = vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)];
+
+        animation.tick(0.5);
+        assert_eq!(animation.position(turtle, (100.0, 100.0)), (50.0, 0.0));
+        assert_eq!(animation.direction(turtle, 90.0), 0.0);
+        assert_eq!(animation.line_points(line, points.clone()), vec![(0.0, 0.0), (50.0, 0.0)]);
+
+        animation.tick(1.0);
+        assert_eq!(animation.position(turtle, (100.0, 100.0)), (100.0, 0.0));
+        assert_eq!(animation.direction(turtle, 90.0), 45.0);
+        assert_eq!(animation.line_points(line, points.clone()), vec![(0.0, 0.0), (100.0, 0.0)]);
+
+        animation.tick(10.0);
+        assert!(!animation.is_playing());
+        assert_eq!(animation.line_points(line, points.clone()), points);
+    }
+
+    #[test]
+    fn disabled_animation_shows_everything() {
+        let mut animation = Animation::new();
+        animation.push(Motion::Draw { object: ObjectId(2) }, 1.0);
+        assert!(animation.is_drawn(ObjectId(2)));
+        assert!(!animation.is_playing());
+    }
+}
@@ -1,11 +1,12 @@
 use crate::types::Value;
 use crate::error::Result;
 use crate::vm::VirtualMachine;
+use crate::symbol::{intern, SymbolId};
 use std::fmt::Debug;
 use std::sync::Arc;
 
 pub trait Eval: Debug {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value>;
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value>;
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -19,7 +20,8 @@ pub enum ASTNode {
 }
 
 impl Eval for ASTNode {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value> {
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value> {
+        vm.step()?;
         match self {
             Self::MethodCall(x) => x.eval(vm),
             Self::Assign(x) => x.eval(vm),
@@ -34,7 +36,7 @@ impl Eval for ASTNode {
 impl ASTNode {
     pub fn new_method_call(method: &str, object: &ASTNode, args: &Vec<ASTNode>) -> Self {
         Self::MethodCall(MethodCallImpl {
-            method: method.to_string(),
+            method: intern(method),
             object: Arc::new(object.clone()),
             args: args.into_iter().map(|x| Arc::new(x.clone())).collect(),
         })
@@ -43,7 +45,7 @@ impl ASTNode {
     pub fn new_assign(object: &Option<ASTNode>, target: &str, value_node: &ASTNode) -> Self {
         Self::Assign(AssignImpl {
             object: object.as_ref().map(|x| Arc::new(x.clone())),
-            target: target.to_string(),
+            target: intern(target),
             value_node: Arc::new(value_node.clone()),
         })
     }
@@ -52,7 +54,7 @@ impl ASTNode {
         let object = object.as_ref().map(|x| Arc::new(x.clone()));
         Self::Decl(DeclImpl {
             object,
-            target: target.to_string(),
+            target: intern(target),
         })
     }
 
@@ -62,7 +64,7 @@ impl ASTNode {
 
     pub fn new_block_define(dummy_args: &Vec<&str>, body: &Vec<ASTNode>) -> Self {
         Self::BlockDefine(BlockDefineImpl {
-            dummy_args: dummy_args.iter().map(|x| x.to_string()).collect(),
+            dummy_args: dummy_args.iter().map(|x| intern(x)).collect(),
             body: body.clone().into_iter().map(|x| Arc::new(x)).collect(),
         })
     }
@@ -167,35 +169,44 @@ impl ASTNode {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct MethodCallImpl {
-    pub method: String,
+    pub method: SymbolId,
     pub object: Arc<ASTNode>,
     pub args: Vec<Arc<ASTNode>>,
 }
 
 impl Eval for MethodCallImpl {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value> {
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value> {
         let object_value = self.object.eval(vm)?;
-        let args_value = self.args.clone().into_iter().map(|x| {x.eval(vm)}).collect::<Result<Vec<Value>>>()?;
-
-        vm.call_method(&object_value, vm.to_symbol(self.method.as_str()), &args_value)
+        let mark = vm.push_roots(std::slice::from_ref(&object_value));
+        let args_value = self.args.iter().map(|x| {
+            let value = x.eval(vm)?;
+            vm.push_roots(std::slice::from_ref(&value));
+            Ok(value)
+        }).collect::<Result<Vec<Value>>>();
+
+        let result = args_value.and_then(|args_value| vm.call_method(&object_value, self.method, &args_value));
+        vm.pop_roots(mark);
+        result
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AssignImpl {
     pub object: Option<Arc<ASTNode>>,
-    pub target: String,
+    pub target: SymbolId,
     pub value_node: Arc<ASTNode>,
 }
 
 impl Eval for AssignImpl {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value> {
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value> {
         let value = self.value_node.eval(vm)?;
-        let target = vm.to_symbol(self.target.as_str());
+        let target = self.target;
         match &self.object {
             Some(x) => {
-                let object = vm.get_object_from_value(&x.eval(vm)?)?;
-                object.set_member(target, value);
+                let mark = vm.push_roots(std::slice::from_ref(&value));
+                let object_value = x.eval(vm);
+                vm.pop_roots(mark);
+                vm.assign_member(&object_value?, target, value)?;
                 Ok(Value::Null)
             }
             None => {
@@ -209,20 +220,19 @@ impl Eval for AssignImpl {
 #[derive(Debug, PartialEq, Clone)]
 pub struct DeclImpl {
     pub object: Option<Arc<ASTNode>>,
-    pub target: String,
+    pub target: SymbolId,
 }
 
 impl Eval for DeclImpl {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value> {
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value> {
 
         match &self.object {
             Some(x) => {
-                let object = vm.get_object_from_value(&x.eval(vm)?)?;
-                object.get_member_str(&self.target, vm)
+                let object_value = x.eval(vm)?;
+                vm.get_object_from_value(&object_value)?.get_member(self.target, vm)
             }
             None => {
-                let sym = vm.to_symbol(self.target.as_str());
-                vm.get_value_in_scope(sym)
+                vm.get_value_in_scope(self.target)
             }
         }
     }
@@ -230,12 +240,12 @@ impl Eval for DeclImpl {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct BlockDefineImpl {
-    pub dummy_args: Vec<String>,
+    pub dummy_args: Vec<SymbolId>,
     pub body: Vec<Arc<ASTNode>>,
 }
 
 impl Eval for BlockDefineImpl {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value> {
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value> {
         let block_obj_value = vm.get_block_object_value()?;
         crate::object::block::create(&block_obj_value,
                                      &self.dummy_args,
@@ -261,7 +271,7 @@ pub enum BinaryOperator {
 }
 
 impl BinaryOperator {
-    fn eval(&self, left: &Value, right: &Value) -> Result<Value> {
+    pub(crate) fn eval(&self, left: &Value, right: &Value) -> Result<Value> {
         Ok(match self {
             BinaryOperator::Add => Value::Num(left.as_num()? + right.as_num()?),
             BinaryOperator::Sub => Value::Num(left.as_num()? - right.as_num()?),
@@ -287,7 +297,7 @@ pub struct BinaryOperatorImpl {
 }
 
 impl Eval for BinaryOperatorImpl {
-    fn eval(&self, vm: &VirtualMachine) -> Result<Value> {
+    fn eval(&self, vm: &mut VirtualMachine) -> Result<Value> {
         let left = self.left.eval(vm)?;
         let right = self.right.eval(vm)?;
 
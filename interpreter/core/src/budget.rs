@@ -0,0 +1,96 @@
+use crate::error::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Thread-safe handle that stops the running program at its next step.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn clear(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Step and wall-clock limits of a run, counted from the last `reset`.
+pub struct Budget {
+    max_steps: Option<u64>,
+    time_limit: Option<Duration>,
+    steps: u64,
+    deadline: Option<Instant>,
+    cancel_handle: CancelHandle,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self {
+            max_steps: None,
+            time_limit: None,
+            steps: 0,
+            deadline: None,
+            cancel_handle: CancelHandle::new(),
+        }
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
+
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.time_limit = time_limit;
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        // `Instant::now` is unavailable on wasm32, so only touch the clock when asked to.
+        self.deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        self.cancel_handle.clear();
+    }
+
+    pub fn step(&mut self) -> Result<()> {
+        if self.cancel_handle.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        self.steps += 1;
+        if let Some(max_steps) = self.max_steps {
+            if self.steps > max_steps {
+                return Err(Error::StepLimitExceeded);
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(Error::TimeLimitExceeded);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
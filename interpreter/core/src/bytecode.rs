@@ -0,0 +1,228 @@
+use crate::ast::{ASTNode, BinaryOperator};
+use crate::error::Result;
+use crate::gc::Trace;
+use crate::symbol::SymbolId;
+use crate::types::Value;
+use crate::vm::{ObjectId, VirtualMachine};
+use std::sync::{Arc, OnceLock};
+
+// Every instruction except `Pop` stands for exactly one AST node, so the
+// step budget is charged the same as in the tree walker.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    PushConst(usize),
+    LoadVar(SymbolId),
+    LoadMember(SymbolId),
+    StoreVar(SymbolId),
+    StoreMember(SymbolId),
+    Call(SymbolId, usize),
+    MakeBlock(usize),
+    BinaryOp(BinaryOperator),
+    Pop,
+}
+
+// The internal value of a block object. The tree walker evaluates `body`;
+// `chunk` is compiled on first use unless the block came from bytecode.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockCode {
+    pub dummy_args: Vec<SymbolId>,
+    pub body: Vec<Arc<ASTNode>>,
+    chunk: OnceLock<Arc<Chunk>>,
+}
+
+impl BlockCode {
+    pub fn new(dummy_args: &Vec<SymbolId>, body: &Vec<Arc<ASTNode>>) -> Self {
+        Self {
+            dummy_args: dummy_args.clone(),
+            body: body.clone(),
+            chunk: OnceLock::new(),
+        }
+    }
+
+    pub fn compile(dummy_args: &Vec<SymbolId>, body: &Vec<Arc<ASTNode>>) -> Self {
+        let block = Self::new(dummy_args, body);
+        block.chunk();
+        block
+    }
+
+    pub fn chunk(&self) -> &Arc<Chunk> {
+        self.chunk.get_or_init(|| Arc::new(Chunk::compile_body(&self.body)))
+    }
+}
+
+impl Trace for BlockCode {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        self.body.trace(mark);
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub blocks: Vec<Arc<BlockCode>>,
+}
+
+impl Chunk {
+    pub fn compile(ast: &ASTNode) -> Self {
+        let mut chunk = Chunk::default();
+        chunk.emit_node(ast);
+        chunk
+    }
+
+    pub fn compile_body(body: &[Arc<ASTNode>]) -> Self {
+        let mut chunk = Chunk::default();
+        for (i, statement) in body.iter().enumerate() {
+            if i > 0 {
+                chunk.code.push(Instruction::Pop);
+            }
+            chunk.emit_node(statement);
+        }
+        chunk
+    }
+
+    fn emit_node(&mut self, ast: &ASTNode) {
+        match ast {
+            ASTNode::MethodCall(x) => {
+                self.emit_node(&x.object);
+                for arg in x.args.iter() {
+                    self.emit_node(arg);
+                }
+                self.code.push(Instruction::Call(x.method, x.args.len()));
+            }
+            ASTNode::Assign(x) => {
+                self.emit_node(&x.value_node);
+                match &x.object {
+                    Some(object) => {
+                        self.emit_node(object);
+                        self.code.push(Instruction::StoreMember(x.target));
+                    }
+                    None => self.code.push(Instruction::StoreVar(x.target)),
+                }
+            }
+            ASTNode::Decl(x) => {
+                match &x.object {
+                    Some(object) => {
+                        self.emit_node(object);
+                        self.code.push(Instruction::LoadMember(x.target));
+                    }
+                    None => self.code.push(Instruction::LoadVar(x.target)),
+                }
+            }
+            ASTNode::StaticValue(v) => {
+                self.constants.push(v.clone());
+                self.code.push(Instruction::PushConst(self.constants.len() - 1));
+            }
+            ASTNode::BlockDefine(x) => {
+                self.blocks.push(Arc::new(BlockCode::compile(&x.dummy_args, &x.body)));
+                self.code.push(Instruction::MakeBlock(self.blocks.len() - 1));
+            }
+            ASTNode::DoBinaryOperator(x) => {
+                self.emit_node(&x.left);
+                self.emit_node(&x.right);
+                self.code.push(Instruction::BinaryOp(x.operator.clone()));
+            }
+        }
+    }
+}
+
+pub fn run(chunk: &Chunk, vm: &mut VirtualMachine) -> Result<Value> {
+    let mut stack: Vec<Value> = Vec::with_capacity(8);
+    for instruction in chunk.code.iter() {
+        if let Instruction::Pop = instruction {
+            stack.pop();
+            continue;
+        }
+        vm.step()?;
+
+        let value = match instruction {
+            Instruction::PushConst(i) => chunk.constants[*i].clone(),
+            Instruction::LoadVar(sym) => vm.get_value_in_scope(*sym)?,
+            Instruction::LoadMember(sym) => {
+                let object = vm.get_object_from_value(&pop(&mut stack))?;
+                object.get_member(*sym, vm)?
+            }
+            Instruction::StoreVar(sym) => {
+                vm.assign(*sym, &pop(&mut stack))?;
+                Value::Null
+            }
+            Instruction::StoreMember(sym) => {
+                let object = pop(&mut stack);
+                vm.assign_member(&object, *sym, pop(&mut stack))?;
+                Value::Null
+            }
+            Instruction::Call(method, argc) => {
+                let args = stack.split_off(stack.len() - argc);
+                let this = pop(&mut stack);
+                let mark = vm.push_roots(&stack);
+                let result = vm.call_method(&this, *method, &args);
+                vm.pop_roots(mark);
+                result?
+            }
+            Instruction::MakeBlock(i) => {
+                let block_obj_value = vm.get_block_object_value()?;
+                crate::object::block::create_from_code(&block_obj_value, chunk.blocks[*i].clone(), vm)?
+            }
+            Instruction::BinaryOp(operator) => {
+                let right = pop(&mut stack);
+                let left = pop(&mut stack);
+                operator.eval(&left, &right)?
+            }
+            Instruction::Pop => unreachable!(),
+        };
+        stack.push(value);
+    }
+    Ok(stack.pop().unwrap_or(Value::Null))
+}
+
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("bytecode stack underflow")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{ASTNode, BinaryOperator};
+    use crate::bytecode::{Chunk, Instruction};
+    use crate::symbol::intern;
+    use crate::types::Value;
+    use crate::vm::VirtualMachine;
+
+    #[test]
+    fn compile_method_call() {
+        let chunk = Chunk::compile(&ASTNode::new_method_call(
+            "歩く",
+            &ASTNode::new_decl(&None, "かめた"),
+            &vec![ASTNode::new_add(
+                &ASTNode::new_static_value(&Value::Num(1.0)),
+                &ASTNode::new_static_value(&Value::Num(2.0)),
+            )],
+        ));
+
+        assert_eq!(chunk.code, vec![
+            Instruction::LoadVar(intern("かめた")),
+            Instruction::PushConst(0),
+            Instruction::PushConst(1),
+            Instruction::BinaryOp(BinaryOperator::Add),
+            Instruction::Call(intern("歩く"), 1),
+        ]);
+        assert_eq!(chunk.constants, vec![Value::Num(1.0), Value::Num(2.0)]);
+    }
+
+    #[test]
+    fn compile_body_keeps_last_value() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+        let body = vec![
+            ASTNode::new_assign(&None, "てすと", &ASTNode::new_static_value(&Value::Num(1.0))),
+            ASTNode::new_add(
+                &ASTNode::new_decl(&None, "てすと"),
+                &ASTNode::new_static_value(&Value::Num(2.0)),
+            ),
+        ].into_iter().map(std::sync::Arc::new).collect::<Vec<_>>();
+        let chunk = Chunk::compile_body(&body);
+
+        assert_eq!(chunk.code[2], Instruction::Pop);
+        assert_eq!(crate::bytecode::run(&chunk, &mut vm).unwrap(), Value::Num(3.0));
+        assert_eq!(crate::bytecode::run(&Chunk::compile_body(&[]), &mut vm).unwrap(), Value::Null);
+    }
+}
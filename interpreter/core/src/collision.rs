@@ -0,0 +1,175 @@
+use crate::error::Result;
+use crate::object::{line, shape, Object};
+use crate::symbol::{intern, SymbolId};
+use crate::types::Value;
+use crate::vm::{ObjectId, VirtualMachine};
+use std::collections::HashSet;
+use utilities::geometry::{Bounds, Vector2D};
+
+// About half the turtle image at 大きさ 1.
+const TURTLE_RADIUS: f64 = 16.0;
+
+/// The pairs found touching by the last pass, so 衝突 runs once when two
+/// objects meet instead of on every step they stay together.
+pub struct Collisions {
+    handler: SymbolId,
+    // set once any object is given 衝突; until then no pass is needed
+    watching: bool,
+    touching: HashSet<(ObjectId, ObjectId)>,
+    // set while handlers run, so the moves they make do not start a pass
+    running: bool,
+}
+
+impl Collisions {
+    pub fn new() -> Self {
+        Self { handler: intern("衝突"), watching: false, touching: HashSet::new(), running: false }
+    }
+
+    // Called for every member assignment.
+    pub fn member_assigned(&mut self, member: SymbolId) {
+        if member == self.handler {
+            self.watching = true;
+        }
+    }
+}
+
+impl Default for Collisions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Body {
+    id: ObjectId,
+    bounds: Bounds,
+    // the turtle that drew it; a turtle never collides with its own drawings
+    owner: Option<ObjectId>,
+}
+
+impl Body {
+    fn related(&self, id: ObjectId, owner: Option<ObjectId>) -> bool {
+        self.owner == Some(id) || owner == Some(self.id)
+    }
+}
+
+fn owner_of(obj: &Object, owner: SymbolId, vm: &VirtualMachine) -> Option<ObjectId> {
+    obj.get_member(owner, vm).ok().and_then(|owner| owner.as_object_id().ok())
+}
+
+enum Kind {
+    Turtle,
+    Line,
+    Shape,
+}
+
+// Cheap to tell, so objects that are not shown are dropped before anything
+// else is looked up.
+fn kind_of(obj: &Object, vm: &VirtualMachine) -> Option<Kind> {
+    let prototype = |name: &str| vm.get_object_id_in_assigns(intern(name)).ok();
+    // 図形 have no visible member and are always shown
+    if let Ok(false) = obj.get_member_str("visible", vm).and_then(|visible| visible.as_bool()) {
+        return None;
+    }
+    if !vm.animation().is_drawn(obj.id()) {
+        return None;
+    }
+    if obj.is_subclass(prototype("タートル")?, vm) {
+        Some(Kind::Turtle)
+    } else if obj.is_subclass(prototype("線")?, vm) {
+        Some(Kind::Line)
+    } else if obj.is_subclass(prototype("図形")?, vm) {
+        Some(Kind::Shape)
+    } else {
+        None
+    }
+}
+
+// Where the object is shown, so while animating, objects collide when the
+// user sees them meet rather than when the program put them there.
+fn bounds_of(obj: &Object, kind: Kind, vm: &VirtualMachine) -> Option<Bounds> {
+    let num = |name: &str| obj.get_member_str(name, vm).ok()?.as_num().ok();
+    let to_vectors = |points: Vec<(f64, f64)>| points.into_iter().map(|(x, y)| Vector2D { x, y }).collect();
+    let animation = vm.animation();
+    match kind {
+        Kind::Turtle => {
+            let (x, y) = animation.position(obj.id(), (num("x")?, num("y")?));
+            Some(Bounds::Circle { center: Vector2D { x, y }, radius: TURTLE_RADIUS * num("scale")? })
+        }
+        Kind::Line => Some(Bounds::Polyline(to_vectors(animation.line_points(obj.id(), line::points(obj, vm).ok()?)))),
+        Kind::Shape => Some(Bounds::Polygon(to_vectors(shape::points(obj, vm).ok()?))),
+    }
+}
+
+// Only pairs with a handler on one side can collide, so everything else,
+// e.g. a turtle's long trail, gets its bounds built only when some
+// handler-bearing object other than its owner might meet it.
+fn touching(handler: SymbolId, vm: &VirtualMachine) -> HashSet<(ObjectId, ObjectId)> {
+    let (with_handler, others): (Vec<&Object>, Vec<&Object>) = vm.get_object_heap().values()
+        .partition(|obj| obj.get_member(handler, vm).is_ok());
+    let owner_member = intern("owner");
+    let handlers: Vec<Body> = with_handler.into_iter()
+        .filter_map(|obj| Some(Body {
+            id: obj.id(),
+            bounds: bounds_of(obj, kind_of(obj, vm)?, vm)?,
+            owner: owner_of(obj, owner_member, vm),
+        }))
+        .collect();
+    if handlers.is_empty() {
+        return HashSet::new();
+    }
+    let pair = |a: ObjectId, b: ObjectId| if a.0 < b.0 { (a, b) } else { (b, a) };
+
+    let mut touching = HashSet::new();
+    for (i, a) in handlers.iter().enumerate() {
+        for b in handlers[i + 1..].iter() {
+            if !a.related(b.id, b.owner) && a.bounds.intersects(&b.bounds) {
+                touching.insert(pair(a.id, b.id));
+            }
+        }
+    }
+    for obj in others {
+        let Some(kind) = kind_of(obj, vm) else { continue };
+        let owner = owner_of(obj, owner_member, vm);
+        if handlers.iter().all(|a| a.related(obj.id(), owner)) {
+            continue;
+        }
+        let Some(bounds) = bounds_of(obj, kind, vm) else { continue };
+        for a in handlers.iter().filter(|a| !a.related(obj.id(), owner)) {
+            if a.bounds.intersects(&bounds) {
+                touching.insert(pair(a.id, obj.id()));
+            }
+        }
+    }
+    touching
+}
+
+// Runs after anything moves. Each pair that has just started touching gets
+// 衝突 called on both objects, with the other one as the argument.
+pub fn detect(vm: &mut VirtualMachine) -> Result<()> {
+    let collisions = vm.collisions_mut();
+    if collisions.running || !collisions.watching {
+        return Ok(());
+    }
+    let touching = touching(collisions.handler, vm);
+
+    let collisions = vm.collisions_mut();
+    let mut met: Vec<_> = touching.difference(&collisions.touching).copied().collect();
+    met.sort_by_key(|(a, b)| (a.0, b.0));
+    collisions.touching = touching;
+    collisions.running = true;
+    let result = met.into_iter().try_for_each(|(a, b)| {
+        notify(a, b, vm)?;
+        notify(b, a, vm)
+    });
+    vm.collisions_mut().running = false;
+    result
+}
+
+fn notify(this: ObjectId, other: ObjectId, vm: &mut VirtualMachine) -> Result<()> {
+    let handler = intern("衝突");
+    let has_handler = vm.get_object(this).is_ok_and(|obj| obj.get_member(handler, vm).is_ok());
+    if has_handler {
+        vm.call_method(&Value::ObjectReference(this), handler, &vec![Value::ObjectReference(other)])?;
+    }
+    Ok(())
+}
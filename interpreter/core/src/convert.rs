@@ -0,0 +1,218 @@
+use crate::error::{Error, Result};
+use crate::types::Value;
+use crate::vm::{ObjectId, VirtualMachine};
+use std::slice::Iter;
+
+// For sizes, speeds and intervals; NaN is refused too.
+pub fn positive(n: f64) -> Result<f64> {
+    if n.is_nan() || n <= 0.0 {
+        return Err(Error::ArgumentError);
+    }
+    Ok(n)
+}
+
+/// Conversion of a script value into a Rust argument of a native method.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self>;
+
+    // Takes this parameter's share of the call arguments; one value by default.
+    fn from_args(args: &mut Iter<Value>) -> Result<Self> {
+        Self::from_value(args.next().ok_or(Error::ArgumentError)?)
+    }
+}
+
+/// Conversion of a Rust return value back into a script value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_num().map_err(|_| Error::ArgumentError)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_bool().map_err(|_| Error::ArgumentError)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Str(s) => Ok(s.clone()),
+            _ => Err(Error::ArgumentError),
+        }
+    }
+}
+
+impl FromValue for ObjectId {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_object_id().map_err(|_| Error::ArgumentError)
+    }
+}
+
+// An omitted trailing argument or 無し becomes `None`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            v => T::from_value(v).map(Some),
+        }
+    }
+
+    fn from_args(args: &mut Iter<Value>) -> Result<Self> {
+        match args.next() {
+            Some(v) => Self::from_value(v),
+            None => Ok(None),
+        }
+    }
+}
+
+// Collects all remaining arguments, so it only makes sense as the last parameter.
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(vec![T::from_value(value)?])
+    }
+
+    fn from_args(args: &mut Iter<Value>) -> Result<Self> {
+        args.map(T::from_value).collect()
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Num(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::Str(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::Str(self.to_string())
+    }
+}
+
+impl IntoValue for ObjectId {
+    fn into_value(self) -> Value {
+        Value::ObjectReference(self)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map(IntoValue::into_value).unwrap_or(Value::Null)
+    }
+}
+
+/// A Rust function taking the receiver, typed arguments and the VM.
+/// `Args` only tells the implementations for different arities apart.
+pub trait TypedMethod<Args>: Send + Sync + 'static {
+    fn call(&self, this: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value>;
+}
+
+macro_rules! impl_typed_method {
+    ($($arg:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<F, This, $($arg,)* R> TypedMethod<(This, $($arg,)*)> for F
+            where F: Fn(This, $($arg,)* &mut VirtualMachine) -> Result<R> + Send + Sync + 'static,
+                  This: FromValue,
+                  $($arg: FromValue,)*
+                  R: IntoValue {
+            fn call(&self, this: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+                let this = This::from_value(this)?;
+                let mut args = args.iter();
+                $(let $arg = $arg::from_args(&mut args)?;)*
+                if args.next().is_some() {
+                    return Err(Error::ArgumentError);
+                }
+                Ok(self(this, $($arg,)* vm)?.into_value())
+            }
+        }
+    };
+}
+
+impl_typed_method!();
+impl_typed_method!(A1);
+impl_typed_method!(A1, A2);
+impl_typed_method!(A1, A2, A3);
+impl_typed_method!(A1, A2, A3, A4);
+
+/// Adapts a typed function so it can be passed to `add_method`.
+pub fn typed<Args, F: TypedMethod<Args>>(method: F)
+    -> impl Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync + 'static {
+    move |this, args, vm| method.call(this, args, vm)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::typed;
+    use crate::error::{Error, Result};
+    use crate::types::Value;
+    use crate::vm::VirtualMachine;
+
+    fn label(_this: Value, name: String, times: Option<f64>, _vm: &mut VirtualMachine) -> Result<String> {
+        Ok(name.repeat(times.unwrap_or(1.0) as usize))
+    }
+
+    fn sum(_this: Value, xs: Vec<f64>, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(xs.iter().sum())
+    }
+
+    #[test]
+    fn typed_arguments() {
+        let mut vm = VirtualMachine::new();
+        let label = typed(label);
+        let name = Value::Str("かめ".to_string());
+
+        assert_eq!(label(&Value::Null, &vec![name.clone()], &mut vm).unwrap(),
+                   Value::Str("かめ".to_string()));
+        assert_eq!(label(&Value::Null, &vec![name.clone(), Value::Num(2.0)], &mut vm).unwrap(),
+                   Value::Str("かめかめ".to_string()));
+        assert!(matches!(label(&Value::Null, &vec![], &mut vm), Err(Error::ArgumentError)));
+        assert!(matches!(label(&Value::Null, &vec![Value::Num(1.0)], &mut vm), Err(Error::ArgumentError)));
+        assert!(matches!(label(&Value::Null, &vec![name.clone(), Value::Num(2.0), Value::Null], &mut vm),
+                         Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn rest_arguments() {
+        let mut vm = VirtualMachine::new();
+        let sum = typed(sum);
+
+        assert_eq!(sum(&Value::Null, &vec![], &mut vm).unwrap(), Value::Num(0.0));
+        assert_eq!(sum(&Value::Null, &vec![Value::Num(1.0), Value::Num(2.0)], &mut vm).unwrap(),
+                   Value::Num(3.0));
+        assert!(matches!(sum(&Value::Null, &vec![Value::Bool(true)], &mut vm), Err(Error::ArgumentError)));
+    }
+}
@@ -5,6 +5,11 @@ pub enum Error {
     MemberNotFound,
     ArgumentError,
     Runtime,
+    SyntaxError,
+    StepLimitExceeded,
+    TimeLimitExceeded,
+    Cancelled,
+    RecursionLimitExceeded,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
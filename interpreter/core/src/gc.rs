@@ -0,0 +1,99 @@
+use crate::ast::ASTNode;
+use crate::types::Value;
+use crate::vm::ObjectId;
+use std::any::Any;
+use std::sync::Arc;
+
+/// Reports every object reachable from a value to the collector.
+pub trait Trace {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId));
+}
+
+/// Data a native prototype keeps inside an object, e.g. the body of a block.
+pub trait InternalValue: Any + Send + Sync + Trace {
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any + Send + Sync + Trace> InternalValue for T {
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Trace for Value {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        if let Value::ObjectReference(id) = self {
+            mark(*id);
+        }
+    }
+}
+
+impl Trace for ObjectId {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        mark(*self);
+    }
+}
+
+impl Trace for String {
+    fn trace(&self, _mark: &mut dyn FnMut(ObjectId)) {}
+}
+
+impl Trace for f64 {
+    fn trace(&self, _mark: &mut dyn FnMut(ObjectId)) {}
+}
+
+impl<T: Trace> Trace for Vec<T> {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        for x in self.iter() {
+            x.trace(mark);
+        }
+    }
+}
+
+impl<T: Trace> Trace for Option<T> {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        if let Some(x) = self {
+            x.trace(mark);
+        }
+    }
+}
+
+impl<T: Trace> Trace for Arc<T> {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        self.as_ref().trace(mark);
+    }
+}
+
+impl<A: Trace, B: Trace> Trace for (A, B) {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        self.0.trace(mark);
+        self.1.trace(mark);
+    }
+}
+
+impl Trace for ASTNode {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        match self {
+            ASTNode::MethodCall(x) => {
+                x.object.trace(mark);
+                x.args.trace(mark);
+            }
+            ASTNode::Assign(x) => {
+                x.object.trace(mark);
+                x.value_node.trace(mark);
+            }
+            ASTNode::Decl(x) => x.object.trace(mark),
+            ASTNode::StaticValue(v) => v.trace(mark),
+            ASTNode::BlockDefine(x) => x.body.trace(mark),
+            ASTNode::DoBinaryOperator(x) => {
+                x.left.trace(mark);
+                x.right.trace(mark);
+            }
+        }
+    }
+}
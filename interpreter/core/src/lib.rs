@@ -2,8 +2,18 @@ pub mod vm;
 pub mod object;
 pub mod types;
 pub mod ast;
-mod error;
+pub mod error;
 pub mod symbol;
+pub mod budget;
+pub mod gc;
+pub mod bytecode;
+pub mod prototype;
+pub mod convert;
+pub mod module;
+pub mod animation;
+pub mod scheduler;
+#[cfg(feature = "turtle")]
+pub mod collision;
 
 #[cfg(test)]
 mod tests {
@@ -12,9 +22,11 @@ mod tests {
     use crate::types::Value;
     use crate::vm::VirtualMachine;
     use crate::ast::{ASTNode, Eval};
+    use crate::error::Error;
+    use std::time::Duration;
 
     fn setup() -> VirtualMachine {
-        let vm = VirtualMachine::new();
+        let mut vm = VirtualMachine::new();
         let create_symbol = vm.to_symbol("作る");
         let mut root = ObjectBody::empty();
         root.add_method(create_symbol, object::root::create);
@@ -23,8 +35,7 @@ mod tests {
         let root_symbol = vm.to_symbol("ルート");
         vm.assign(root_symbol, &Value::ObjectReference(root_obj_id)).unwrap();
 
-        let mut turtle = ObjectBody::new(
-                    &Some(vm.get_object(root_obj_id).unwrap().clone()));
+        let mut turtle = ObjectBody::new(Some(root_obj_id));
         let turtle_obj_id = vm.allocate(turtle).unwrap();
         let turtle_symbol = vm.to_symbol("タートル");
         vm.assign(turtle_symbol, &Value::ObjectReference(turtle_obj_id)).unwrap();
@@ -34,31 +45,32 @@ mod tests {
 
     #[test]
     fn create() {
-        let vm = setup();
+        let mut vm = setup();
         assert_eq!(vm.object_heap_borrow().len(), 2);
         ASTNode::new_method_call(
             "作る",
             &ASTNode::new_decl(&None, "ルート"),
             &vec![],
-        ).eval(&vm);
+        ).eval(&mut vm);
 
         assert_eq!(vm.object_heap_borrow().len(), 3);
     }
 
     #[test]
     fn call_parent_method() {
-        let vm = setup();
+        let mut vm = setup();
         assert_eq!(vm.object_heap_borrow().len(), 2);
         ASTNode::new_method_call(
             "作る",
             &ASTNode::new_decl(&None, "タートル"),
             &vec![],
-        ).eval(&vm);
+        ).eval(&mut vm);
 
         assert_eq!(vm.object_heap_borrow().len(), 3);
     }
 
     #[test]
+    #[cfg(feature = "turtle")]
     fn define_block_and_call_block() {
         let mut vm = VirtualMachine::new();
         vm.initialize();
@@ -83,6 +95,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "turtle")]
     fn define_block_and_call_block2() {
         let mut vm = VirtualMachine::new();
         vm.initialize();
@@ -129,6 +142,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "turtle")]
     fn test_repeat_4times() {
         let mut vm = VirtualMachine::new();
         vm.initialize();
@@ -161,4 +175,169 @@ mod tests {
             "y"
         )).unwrap(), Value::Num(0.0));
     }
+
+    fn repeat_empty_block(times: f64) -> ASTNode {
+        ASTNode::new_method_call(
+            "繰り返す",
+            &ASTNode::new_block_define(&vec![], &vec![]),
+            &vec![ASTNode::StaticValue(Value::Num(times))],
+        )
+    }
+
+    #[test]
+    fn step_limit_stops_long_repeat() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+        vm.set_step_limit(Some(1000));
+        vm.reset_budget();
+
+        let result = vm.eval(&repeat_empty_block(1000000000.0));
+        assert!(matches!(result, Err(Error::StepLimitExceeded)));
+
+        vm.reset_budget();
+        assert!(vm.eval(&repeat_empty_block(10.0)).is_ok());
+    }
+
+    #[test]
+    fn time_limit_stops_long_repeat() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+        vm.set_time_limit(Some(Duration::from_millis(10)));
+        vm.reset_budget();
+
+        let result = vm.eval(&repeat_empty_block(1000000000.0));
+        assert!(matches!(result, Err(Error::TimeLimitExceeded)));
+    }
+
+    #[test]
+    fn cancel_handle_stops_until_reset() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        let handle = vm.cancel_handle();
+        let canceller = std::thread::spawn(move || handle.cancel());
+        canceller.join().unwrap();
+
+        let result = vm.eval(&repeat_empty_block(1000000000.0));
+        assert!(matches!(result, Err(Error::Cancelled)));
+
+        vm.reset_budget();
+        assert!(vm.eval(&repeat_empty_block(10.0)).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "turtle")]
+    fn recursion_limit_stops_self_calling_block() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        vm.eval(&ASTNode::new_assign(
+            &None, "かめた",
+            &ASTNode::new_method_call(
+                "作る",
+                &ASTNode::new_decl(&None, "タートル"),
+                &vec![],
+            ))).unwrap();
+        vm.eval(&ASTNode::new_assign(
+            &Some(ASTNode::new_decl(&None, "かめた")),
+            "再帰",
+            &ASTNode::new_block_define(&vec![], &vec![
+                ASTNode::new_method_call(
+                    "再帰",
+                    &ASTNode::new_decl(&None, "かめた"),
+                    &vec![],
+                ),
+            ]),
+        )).unwrap();
+
+        let result = vm.eval(&ASTNode::new_method_call(
+            "再帰",
+            &ASTNode::new_decl(&None, "かめた"),
+            &vec![],
+        ));
+        assert!(matches!(result, Err(Error::RecursionLimitExceeded)));
+        assert_eq!(vm.call_depth(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "turtle")]
+    fn collect_garbage_after_loop() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        vm.eval(&ASTNode::new_assign(
+            &None, "かめた",
+            &ASTNode::new_method_call(
+                "作る",
+                &ASTNode::new_decl(&None, "タートル"),
+                &vec![],
+            ))).unwrap();
+        let before_loop = vm.object_heap_borrow().len();
+
+        vm.eval(&ASTNode::new_method_call(
+            "繰り返す",
+            &ASTNode::new_block_define(&vec![], &vec![
+                ASTNode::new_method_call(
+                    "作る",
+                    &ASTNode::new_decl(&None, "ルート"),
+                    &vec![],
+                ),
+                ASTNode::new_method_call(
+                    "ならば",
+                    &ASTNode::new_block_define(&vec![], &vec![
+                        ASTNode::new_static_value(&Value::Bool(true)),
+                    ]),
+                    &vec![],
+                ),
+                ASTNode::new_method_call(
+                    "歩く",
+                    &ASTNode::new_decl(&None, "かめた"),
+                    &vec![ASTNode::new_static_value(&Value::Num(1.0))],
+                ),
+            ]),
+            &vec![ASTNode::new_static_value(&Value::Num(100.0))],
+        )).unwrap();
+        assert!(vm.object_heap_borrow().len() > before_loop + 300);
+
+        vm.collect_garbage();
+
+        // only the line drawn on screen survives, holding all 100 steps
+        assert_eq!(vm.object_heap_borrow().len(), before_loop + 1);
+        assert_eq!(vm.eval(&ASTNode::new_decl(
+            &Some(ASTNode::new_decl(&None, "かめた")),
+            "x"
+        )).unwrap(), Value::Num(100.0));
+    }
+
+    #[test]
+    #[cfg(feature = "turtle")]
+    fn closure_method_keeps_captured_state() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let turtle_id = vm.get_object_in_assigns_from_symbol("タートル").unwrap().id();
+        vm.get_object_mut(turtle_id).unwrap().add_method_str("数える", move |this, _args, _vm| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(this.clone())
+        });
+
+        vm.eval(&ASTNode::new_method_call(
+            "繰り返す",
+            &ASTNode::new_block_define(&vec![], &vec![
+                ASTNode::new_method_call(
+                    "数える",
+                    &ASTNode::new_decl(&None, "タートル"),
+                    &vec![],
+                ),
+            ]),
+            &vec![ASTNode::new_static_value(&Value::Num(3.0))],
+        )).unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
 }
@@ -0,0 +1,299 @@
+use crate::convert::typed;
+use crate::error::Result;
+use crate::object::{self, ObjectBody};
+use crate::prototype::PrototypeBuilder;
+use crate::symbol::intern;
+use crate::types::Value;
+use crate::vm::VirtualMachine;
+#[cfg(feature = "color")]
+use utilities::color::Rgb;
+
+/// A set of prototypes installed into a VM as one unit.
+pub trait Module {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()>;
+}
+
+impl<F: Fn(&mut VirtualMachine) -> Result<()>> Module for F {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        self(vm)
+    }
+}
+
+/// ルート, ブロック, 配列 and the Condition object behind ならば. 配列 is
+/// here because reflection and other natives return their results in one.
+pub struct CoreModule;
+
+impl Module for CoreModule {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        let mut root = ObjectBody::empty();
+        root.add_method_str("作る", object::root::create);
+        root.add_method_str("複製", object::root::clone);
+        root.add_method_str("メソッド一覧", typed(object::root::method_list));
+        root.add_method_str("変数一覧", typed(object::root::variable_list));
+        root.add_method_str("親", typed(object::root::parent));
+        root.add_method_str("種類", typed(object::root::is_a));
+        root.add_method_str("親メソッド", object::root::call_super);
+        root.add_method_str("混ぜる", typed(object::root::mixin));
+        let root_obj_id = vm.allocate(root)?;
+        vm.assign(intern("ルート"), &Value::ObjectReference(root_obj_id))?;
+
+        PrototypeBuilder::new("ブロック")
+            .method("実行", object::block::exec)
+            .method("繰り返す", typed(object::block::repeat))
+            .method("ならば", object::block::if_)
+            .install(vm)?;
+
+        let array_id = PrototypeBuilder::new("配列")
+            .method("作る", typed(object::array::create))
+            .method("書く", typed(object::array::push))
+            .method("読む", typed(object::array::get))
+            .method("要素数", typed(object::array::len))
+            .method("それぞれ実行", typed(object::array::for_each))
+            .install(vm)?;
+        vm.get_object_mut(array_id)?.set_internal_value(std::sync::Arc::new(Vec::<Value>::new()));
+
+        object::condition::create_super_object(vm)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "color")]
+const COLOR_NAMES: [(&str, Rgb); 5] = [
+    ("黒", Rgb::BLACK),
+    ("白", Rgb { r: 255, g: 255, b: 255 }),
+    ("赤", Rgb { r: 255, g: 0, b: 0 }),
+    ("緑", Rgb { r: 0, g: 128, b: 0 }),
+    ("青", Rgb { r: 0, g: 0, b: 255 }),
+];
+
+/// 色 and the named colours 黒, 白, 赤, 緑 and 青.
+#[cfg(feature = "color")]
+pub struct ColorModule;
+
+#[cfg(feature = "color")]
+impl Module for ColorModule {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        let color_id = PrototypeBuilder::new("色")
+            .method("作る", typed(object::color::create))
+            .method("HSV", typed(object::color::create_hsv))
+            .method("色を混ぜる", typed(object::color::mix))
+            .method("ランダム", typed(object::color::random))
+            .install(vm)?;
+        vm.get_object_mut(color_id)?.set_internal_value(std::sync::Arc::new(Rgb::BLACK));
+
+        for (name, color) in COLOR_NAMES.iter() {
+            let color = object::color::create_value(*color, vm)?;
+            vm.assign(intern(name), &Value::ObjectReference(color))?;
+        }
+        Ok(())
+    }
+}
+
+/// タートル, the 線, curves and 図形 it draws, and the 画面 they are drawn on.
+/// Needs the colour module besides the core one.
+#[cfg(feature = "turtle")]
+pub struct TurtleModule;
+
+#[cfg(feature = "turtle")]
+impl Module for TurtleModule {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        PrototypeBuilder::new("タートル")
+            .member("x", Value::Num(0.0))
+            .member("y", Value::Num(0.0))
+            .member("direction", Value::Num(0.0))
+            .member("visible", Value::Bool(false))
+            .member("pen", Value::Bool(true))
+            .member("line_color", Value::Str(Rgb::BLACK.to_hex()))
+            .member("line_width", Value::Num(1.0))
+            .member("image", Value::Str("ayumi.png".to_string()))
+            .member("scale", Value::Num(1.0))
+            .member("speed", Value::Num(100.0))
+            .method("歩く", typed(object::turtle::walk))
+            .method("右回り", typed(object::turtle::turn_right))
+            .method("左回り", typed(object::turtle::turn_left))
+            .method("戻る", typed(object::turtle::walk_back))
+            .method("移動する", typed(object::turtle::move_to))
+            .method("位置", typed(object::turtle::position))
+            .method("向き", typed(object::turtle::set_direction))
+            .method("中心に戻る", typed(object::turtle::home))
+            .method("距離", typed(object::turtle::distance_to))
+            .method("その方向を向く", typed(object::turtle::face))
+            .method("図形にする", typed(object::turtle::make_shape))
+            .method("円", typed(object::turtle::circle))
+            .method("弧", typed(object::turtle::arc))
+            .method("曲線", typed(object::turtle::curve))
+            .method("消える", typed(object::turtle::hide))
+            .method("現れる", typed(object::turtle::show))
+            .method("変身する", typed(object::turtle::change_image))
+            .method("大きさ", typed(object::turtle::set_scale))
+            .method("速さ", typed(object::turtle::set_speed))
+            .method("ペンなし", typed(object::turtle::pen_up))
+            .method("ペンあり", typed(object::turtle::pen_down))
+            .method("線の色", typed(object::turtle::set_line_color))
+            .method("線の太さ", typed(object::turtle::set_line_width))
+            .method("作る", object::turtle::create)
+            .method("複製", object::turtle::clone)
+            .install(vm)?;
+
+        for name in ["線", "円", "弧", "曲線"].iter() {
+            PrototypeBuilder::new(name)
+                .member("color", Value::Str(Rgb::BLACK.to_hex()))
+                .member("width", Value::Num(1.0))
+                .member("visible", Value::Bool(true))
+                .install(vm)?;
+        }
+
+        PrototypeBuilder::new("図形")
+            .member("x", Value::Num(0.0))
+            .member("y", Value::Num(0.0))
+            .member("direction", Value::Num(0.0))
+            .member("color", Value::Str(Rgb::BLACK.to_hex()))
+            .member("width", Value::Num(1.0))
+            .member("fill", Value::Null)
+            .method("塗る", typed(object::shape::fill))
+            .method("移動する", typed(object::shape::move_to))
+            .method("回転する", typed(object::shape::rotate_by))
+            .install(vm)?;
+
+        PrototypeBuilder::new("画面")
+            .member("background", Value::Str(Rgb::new(255, 255, 255).to_hex()))
+            .method("背景色", typed(object::screen::set_background))
+            .install(vm)?;
+        Ok(())
+    }
+}
+
+/// ボタン.
+#[cfg(feature = "gui")]
+pub struct GuiModule;
+
+#[cfg(feature = "gui")]
+impl Module for GuiModule {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        object::button::create_super_object(vm)?;
+        Ok(())
+    }
+}
+
+/// 数学, a namespace for numeric functions.
+pub struct MathModule;
+
+impl Module for MathModule {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        PrototypeBuilder::new("数学")
+            .method("平方根", typed(object::math::sqrt))
+            .method("絶対値", typed(object::math::abs))
+            .method("切り捨て", typed(object::math::floor))
+            .method("四捨五入", typed(object::math::round))
+            .method("サイン", typed(object::math::sin))
+            .method("コサイン", typed(object::math::cos))
+            .method("最大", typed(object::math::max))
+            .method("最小", typed(object::math::min))
+            .install(vm)?;
+        Ok(())
+    }
+}
+
+/// タイマー, which runs a block at the host's ticks.
+pub struct TimerModule;
+
+impl Module for TimerModule {
+    fn install(&self, vm: &mut VirtualMachine) -> Result<()> {
+        PrototypeBuilder::new("タイマー")
+            .member("interval", Value::Num(1.0))
+            .member("times", Value::Num(10.0))
+            .method("間隔", typed(object::timer::set_interval))
+            .method("回数", typed(object::timer::set_times))
+            .method("実行", typed(object::timer::start))
+            .method("止める", typed(object::timer::stop))
+            .install(vm)?;
+        Ok(())
+    }
+}
+
+/// Every module enabled by the crate features, in installation order.
+pub fn standard_modules() -> Vec<Box<dyn Module>> {
+    let mut modules: Vec<Box<dyn Module>> = vec![Box::new(CoreModule)];
+    #[cfg(feature = "color")]
+    modules.push(Box::new(ColorModule));
+    #[cfg(feature = "turtle")]
+    modules.push(Box::new(TurtleModule));
+    #[cfg(feature = "gui")]
+    modules.push(Box::new(GuiModule));
+    modules.push(Box::new(MathModule));
+    modules.push(Box::new(TimerModule));
+    modules
+}
+
+/// Builds a VM from the core module plus the given ones, e.g. a headless VM
+/// without graphics or one with a host's own prelude.
+pub struct VirtualMachineBuilder {
+    modules: Vec<Box<dyn Module>>,
+}
+
+impl VirtualMachineBuilder {
+    pub fn new() -> Self {
+        Self {
+            modules: vec![Box::new(CoreModule)],
+        }
+    }
+
+    pub fn standard() -> Self {
+        Self {
+            modules: standard_modules(),
+        }
+    }
+
+    pub fn module<M: Module + 'static>(mut self, module: M) -> Self {
+        self.modules.push(Box::new(module));
+        self
+    }
+
+    pub fn build(self) -> Result<VirtualMachine> {
+        let mut vm = VirtualMachine::new();
+        for module in self.modules.iter() {
+            module.install(&mut vm)?;
+        }
+        Ok(vm)
+    }
+}
+
+impl Default for VirtualMachineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module::{MathModule, VirtualMachineBuilder};
+    use crate::prototype::PrototypeBuilder;
+    use crate::types::Value;
+
+    #[test]
+    fn core_only() {
+        let vm = VirtualMachineBuilder::new().build().unwrap();
+
+        assert!(vm.get_object_in_assigns_from_symbol("ルート").is_ok());
+        assert!(vm.get_object_in_assigns_from_symbol("ブロック").is_ok());
+        assert!(vm.get_object_in_assigns_from_symbol("タートル").is_err());
+        assert!(vm.get_object_in_assigns_from_symbol("数学").is_err());
+    }
+
+    #[test]
+    fn custom_prelude() {
+        let mut vm = VirtualMachineBuilder::new()
+            .module(MathModule)
+            .module(|vm: &mut crate::vm::VirtualMachine| {
+                PrototypeBuilder::new("センサー").member("値", Value::Num(0.0)).install(vm)?;
+                Ok(())
+            })
+            .build()
+            .unwrap();
+
+        let math = Value::ObjectReference(vm.get_object_in_assigns_from_symbol("数学").unwrap().id());
+        assert_eq!(vm.call_method(&math, vm.to_symbol("平方根"), &vec![Value::Num(16.0)]).unwrap(),
+                   Value::Num(4.0));
+        assert!(vm.get_object_in_assigns_from_symbol("センサー").is_ok());
+    }
+}
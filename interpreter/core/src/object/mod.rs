@@ -1,306 +1,1016 @@
-use crate::symbol::SymbolId;
+use crate::symbol::{intern, SymbolId};
 use crate::types::Value;
 use crate::error::{Error, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::any::{Any};
 use crate::vm::{VirtualMachine, ObjectId};
+use crate::gc::{InternalValue, Trace};
 use std::fmt::{Debug, Formatter};
-use std::sync::{RwLock, Arc};
+use std::sync::Arc;
 
-type Method = fn(&Value, &Vec<Value>, &VirtualMachine) -> Result<Value>;
+pub type Method = Arc<dyn Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync>;
 
 
 #[derive(Debug)]
 pub struct Object {
     id: ObjectId,
-    body: RwLock<ObjectBody>,
+    body: ObjectBody,
 }
 
 impl Object {
     pub fn empty(id: ObjectId) -> Self {
         Self {
             id,
-            body: RwLock::new(ObjectBody::new(&None))
+            body: ObjectBody::empty(),
         }
     }
 
     pub fn new(id: ObjectId, body: ObjectBody) -> Self {
         Self {
             id,
-            body: RwLock::new(body)
+            body,
         }
     }
 
-    pub fn is_subclass(&self, obj_id: ObjectId) -> bool {
-        if let Some(this_obj) = self.body.read().unwrap().parent.clone() {
-            if this_obj.id == obj_id {
-                return true;
-            }
+    pub fn id(&self) -> ObjectId {
+        self.id
+    }
 
-            this_obj.is_subclass(obj_id)
-        } else {
-            return false;
+    // The first delegate, which is the prototype the object was made from.
+    pub fn parent(&self) -> Option<ObjectId> {
+        self.body.parents.first().copied()
+    }
+
+    pub fn parents(&self) -> &[ObjectId] {
+        &self.body.parents
+    }
+
+    pub fn add_parent(&mut self, parent: ObjectId) {
+        self.body.add_parent(parent)
+    }
+
+    pub fn is_subclass(&self, obj_id: ObjectId, vm: &VirtualMachine) -> bool {
+        self.body.parents.iter().any(|parent_id| {
+            *parent_id == obj_id || vm.get_object(*parent_id)
+                .map(|parent| parent.is_subclass(obj_id, vm))
+                .unwrap_or(false)
+        })
+    }
+
+    // The lookup order: the object itself, then its delegates depth first from
+    // left to right. An ancestor shared by several delegates is visited after
+    // all of them, so a mixin's own slots win over ones it shares with ルート.
+    pub fn ancestors<'a>(&'a self, vm: &'a VirtualMachine) -> Vec<&'a Object> {
+        let mut visits = vec![];
+        self.visit(vm, &mut visits);
+        let mut seen = HashSet::new();
+        let mut chain: Vec<&Object> = visits.into_iter().rev()
+            .filter(|obj| seen.insert(obj.id))
+            .collect();
+        chain.reverse();
+        chain
+    }
+
+    fn visit<'a>(&'a self, vm: &'a VirtualMachine, visits: &mut Vec<&'a Object>) {
+        visits.push(self);
+        for parent in self.body.parents.iter().filter_map(|id| vm.get_object(*id).ok()) {
+            parent.visit(vm, visits);
+        }
+    }
+
+    // Walks single-parent chains without building the whole lookup order,
+    // which only matters once an object with mixins is reached.
+    fn find<T>(&self, vm: &VirtualMachine, f: impl Fn(&Object) -> Option<T>) -> Option<T> {
+        let mut current = self;
+        loop {
+            if let Some(found) = f(current) {
+                return Some(found);
+            }
+            match current.body.parents.as_slice() {
+                [] => return None,
+                [parent] => current = vm.get_object(*parent).ok()?,
+                _ => break,
+            }
         }
+        current.ancestors(vm).into_iter().skip(1).find_map(f)
+    }
+
+    pub fn own_members(&self) -> Vec<(SymbolId, Value)> {
+        let mut members: Vec<(SymbolId, Value)> = self.body.members.iter()
+            .map(|(k, v)| (*k, v.clone())).collect();
+        members.sort_by_key(|(k, _)| k.name());
+        members
     }
 
-    pub fn get_method(&self, symbol: SymbolId) -> Result<Method> {
-        self.body.read().unwrap().get_method(symbol)
+    pub fn own_methods(&self) -> Vec<SymbolId> {
+        let mut methods: Vec<SymbolId> = self.body.methods.keys().copied().collect();
+        methods.sort_by_key(|k| k.name());
+        methods
     }
 
-    pub fn add_method(&self, symbol: SymbolId, method: Method) {
-        self.body.write().unwrap().add_method(symbol, method)
+    // Own and inherited members as seen from this object, so an overridden
+    // member appears once with the nearest value.
+    pub fn members(&self, vm: &VirtualMachine) -> Vec<(String, Value)> {
+        let mut seen = HashSet::new();
+        self.ancestors(vm).into_iter()
+            .flat_map(|obj| obj.own_members())
+            .filter(|(k, _)| seen.insert(*k))
+            .map(|(k, v)| (k.to_string(), v))
+            .collect()
     }
 
-    pub fn add_method_str(&self, symbol: &str, method: Method, vm: &VirtualMachine) {
-        self.add_method(vm.to_symbol(symbol), method)
+    pub fn method_names(&self, vm: &VirtualMachine) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.ancestors(vm).into_iter()
+            .flat_map(|obj| obj.own_methods())
+            .filter(|k| seen.insert(*k))
+            .map(|k| k.to_string())
+            .collect()
     }
 
-    pub fn set_member(&self, symbol: SymbolId, value: Value) {
-        self.body.write().unwrap().set_member(symbol, value)
+    pub fn own_member(&self, symbol: SymbolId) -> Option<Value> {
+        self.body.members.get(&symbol).cloned()
     }
 
-    pub fn get_member(&self, symbol: SymbolId) -> Result<Value> {
-        self.body.read().unwrap().get_member(symbol)
+    pub fn own_method(&self, symbol: SymbolId) -> Option<Method> {
+        self.body.methods.get(&symbol).cloned()
+    }
+
+    pub fn get_method(&self, symbol: SymbolId, vm: &VirtualMachine) -> Result<Method> {
+        self.find(vm, |obj| obj.own_method(symbol)).ok_or(Error::MethodNotFound)
+    }
+
+    pub fn add_method<F>(&mut self, symbol: SymbolId, method: F)
+        where F: Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync + 'static {
+        self.body.add_method(symbol, method)
+    }
+
+    pub fn add_method_str<F>(&mut self, symbol: &str, method: F)
+        where F: Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync + 'static {
+        self.body.add_method_str(symbol, method)
+    }
+
+    pub fn set_member(&mut self, symbol: SymbolId, value: Value) {
+        self.body.set_member(symbol, value)
+    }
+
+    pub fn get_member(&self, symbol: SymbolId, vm: &VirtualMachine) -> Result<Value> {
+        self.find(vm, |obj| obj.own_member(symbol)).ok_or(Error::MemberNotFound)
+    }
+
+    // Also returns the object in the chain that holds the member.
+    pub fn lookup_member(&self, symbol: SymbolId, vm: &VirtualMachine) -> Result<(ObjectId, Value)> {
+        self.find(vm, |obj| obj.own_member(symbol).map(|v| (obj.id, v)))
+            .ok_or(Error::MemberNotFound)
     }
 
     pub fn get_member_str(&self, symbol: &str, vm: &VirtualMachine) -> Result<Value> {
-        self.get_member(vm.to_symbol(symbol))
+        self.get_member(intern(symbol), vm)
     }
 
-    pub fn set_member_str(&self, symbol: &str, value: Value, vm: &VirtualMachine) {
-        self.set_member(vm.to_symbol(symbol), value)
+    pub fn set_member_str(&mut self, symbol: &str, value: Value) {
+        self.body.set_member_str(symbol, value)
     }
 
-    pub fn set_internal_value(&self, internal_value: Arc<dyn Any + Send + Sync>) {
-        self.body.write().unwrap().set_internal_value(internal_value)
+    pub fn set_internal_value(&mut self, internal_value: Arc<dyn InternalValue>) {
+        self.body.set_internal_value(internal_value)
     }
 
     pub fn get_internal_value<T: Clone + Any + Send + Sync>(&self) -> Arc<T> {
-        self.body
-            .write().unwrap().get_internal_value()
+        self.body.get_internal_value()
+    }
+
+    // For objects that may not have been made by the prototype owning `T`,
+    // such as ones that only mix it in.
+    pub fn internal_value<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.body.internal_value()
+    }
+
+    // For values that grow in place, such as a turtle's trail. A value still
+    // shared with a copy made by 複製 is copied first.
+    pub fn internal_value_mut<T: Clone + InternalValue>(&mut self) -> Option<&mut T> {
+        self.body.internal_value_mut()
     }
 }
 
+#[derive(Clone)]
 pub struct ObjectBody {
-    parent: Option<Arc<Object>>,
+    parents: Vec<ObjectId>,
     members: HashMap<SymbolId, Value>,
     methods: HashMap<SymbolId, Method>,
-    internal_value: Option<Arc<dyn Any + Send + Sync>>,
+    internal_value: Option<Arc<dyn InternalValue>>,
+}
+
+impl Trace for Object {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        self.body.trace(mark)
+    }
+}
+
+impl Trace for ObjectBody {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        for parent in self.parents.iter() {
+            mark(*parent);
+        }
+        for v in self.members.values() {
+            v.trace(mark);
+        }
+        if let Some(internal_value) = &self.internal_value {
+            internal_value.trace(mark);
+        }
+    }
 }
 
 impl Debug for ObjectBody {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ObjectBody")
-            .field("parent", &format!("{:?}", self.parent))
+            .field("parents", &self.parents)
             .field("members", &self.members)
+            .field("methods", &self.methods.keys().collect::<Vec<_>>())
             .finish()
     }
 }
 
 impl ObjectBody {
-    pub fn new(parent: &Option<Arc<Object>>) -> Self {
+    pub fn new(parent: Option<ObjectId>) -> Self {
         ObjectBody{
-            parent: parent.clone(),
-            members: parent.as_ref().map(
-                |p| p.body.read().unwrap().members.clone()
-            ).unwrap_or(HashMap::new()),
+            parents: parent.into_iter().collect(),
+            members: HashMap::new(),
             methods: HashMap::new(),
             internal_value: None,
         }
     }
 
     pub fn empty() -> Self {
-        ObjectBody::new(&None)
+        ObjectBody::new(None)
+    }
+
+    // Replaces the first delegate and keeps any mixins after it.
+    pub fn set_parent(&mut self, parent: Option<ObjectId>) {
+        if !self.parents.is_empty() {
+            self.parents.remove(0);
+        }
+        if let Some(parent) = parent {
+            self.parents.retain(|id| *id != parent);
+            self.parents.insert(0, parent);
+        }
     }
 
-    pub fn get_method(&self, symbol: SymbolId) -> Result<Method> {
-        let parent = self.parent.clone();
-        Ok(self.methods
-            .get(&symbol)
-            .map(|x| x.clone())
-            .or_else(|| {
-                if let Some(parent) = parent {
-                    parent.get_method(symbol).ok()
-                } else {
-                    None
-                }
-            })
-            .ok_or(Error::MethodNotFound)?)
+    // Mixins are consulted after the delegates already present.
+    pub fn add_parent(&mut self, parent: ObjectId) {
+        if !self.parents.contains(&parent) {
+            self.parents.push(parent);
+        }
     }
 
-    pub fn add_method(&mut self, symbol: SymbolId, method: Method) {
-        self.methods.insert(symbol, method);
+    pub fn add_method<F>(&mut self, symbol: SymbolId, method: F)
+        where F: Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync + 'static {
+        self.methods.insert(symbol, Arc::new(method));
     }
 
-    pub fn add_method_str(&mut self, symbol: &str, method: Method, vm: &VirtualMachine) {
-        self.add_method(vm.to_symbol(symbol), method)
+    pub fn add_method_str<F>(&mut self, symbol: &str, method: F)
+        where F: Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync + 'static {
+        self.add_method(intern(symbol), method)
     }
 
     pub fn set_member(&mut self, symbol: SymbolId, value: Value) {
         self.members.insert(symbol, value);
     }
 
-    pub fn get_member(&self, symbol: SymbolId) -> Result<Value> {
-        let parent = self.parent.clone();
-
-        Ok(self.members
-            .get(&symbol)
-            .map(|x| x.clone())
-            .or_else(|| {
-                if let Some(parent) = parent {
-                    parent.get_member(symbol).ok()
-                } else {
-                    None
-                }
-            })
-            .ok_or(Error::MemberNotFound)?)
+    pub fn set_member_str(&mut self, symbol: &str, value: Value) {
+        self.set_member(intern(symbol), value)
     }
 
-    pub fn get_member_str(&self, symbol: &str, vm: &VirtualMachine) -> Result<Value> {
-        self.get_member(vm.to_symbol(symbol))
+    pub fn set_internal_value(&mut self, internal_value: Arc<dyn InternalValue>) {
+        self.internal_value = Some(internal_value);
     }
 
-    pub fn set_member_str(&mut self, symbol: &str, value: Value, vm: &VirtualMachine) {
-        self.set_member(vm.to_symbol(symbol), value)
+    pub fn get_internal_value<T: Clone + Any + Send + Sync>(&self) -> Arc<T> {
+        self.internal_value().expect("invalid get internal value")
     }
 
-    pub fn set_internal_value(&mut self, internal_value: Arc<dyn Any + Send + Sync>) {
-        self.internal_value = Some(internal_value);
+    pub fn internal_value<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.internal_value.clone()?.into_any().downcast::<T>().ok()
     }
 
-    pub fn get_internal_value<T: Clone + Any + Send + Sync>(&self) -> Arc<T> {
-        self.internal_value.clone()
-            .expect("invalid get internal value").downcast::<T>()
-            .expect("invalid get internal value").clone()
+    pub fn internal_value_mut<T: Clone + InternalValue>(&mut self) -> Option<&mut T> {
+        if Arc::get_mut(self.internal_value.as_mut()?).is_none() {
+            let copy = (*self.internal_value::<T>()?).clone();
+            self.internal_value = Some(Arc::new(copy));
+        }
+        Arc::get_mut(self.internal_value.as_mut()?)?.as_any_mut().downcast_mut::<T>()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::object::{ObjectBody, Object};
-    use crate::vm::ObjectId;
-    use std::sync::Arc;
+    use crate::object::ObjectBody;
+    use crate::symbol::intern;
+    use crate::types::Value;
+    use crate::vm::{ObjectId, VirtualMachine};
 
     #[test]
     fn is_subclass() {
-        let parent = Object::new(ObjectId(0), ObjectBody::empty());
-        let child = Object::new(ObjectId(1), ObjectBody::new(&Some(Arc::new(parent))));
+        let mut vm = VirtualMachine::new();
+        let parent_id = vm.allocate(ObjectBody::empty()).unwrap();
+        let child_body = ObjectBody::new(Some(parent_id));
+        let child_id = vm.allocate(child_body).unwrap();
+        let child = vm.get_object(child_id).unwrap();
+
+        assert!(child.is_subclass(parent_id, &vm));
+        assert!(!child.is_subclass(ObjectId(3), &vm));
+    }
+
+    #[test]
+    fn multiple_parents() {
+        let mut vm = VirtualMachine::new();
+        let mut root = ObjectBody::empty();
+        root.set_member_str("x", Value::Num(0.0));
+        let root_id = vm.allocate(root).unwrap();
+        let turtle_id = vm.allocate(ObjectBody::new(Some(root_id))).unwrap();
+        let mut button = ObjectBody::new(Some(root_id));
+        button.set_member_str("x", Value::Num(2.0));
+        let button_id = vm.allocate(button).unwrap();
+        let child_id = vm.allocate(ObjectBody::new(Some(turtle_id))).unwrap();
+        vm.add_mixin(child_id, button_id).unwrap();
+        let child = vm.get_object(child_id).unwrap();
+
+        assert!(child.is_subclass(turtle_id, &vm));
+        assert!(child.is_subclass(button_id, &vm));
+        assert_eq!(child.ancestors(&vm).iter().map(|o| o.id()).collect::<Vec<_>>(),
+                   vec![child_id, turtle_id, button_id, root_id]);
+        assert_eq!(child.get_member_str("x", &vm).unwrap(), Value::Num(2.0));
+        assert!(vm.add_mixin(root_id, child_id).is_err());
+    }
 
-        assert!(child.is_subclass(ObjectId(0)));
-        assert!(!child.is_subclass(ObjectId(3)));
+    #[test]
+    fn reflection() {
+        let mut vm = VirtualMachine::new();
+        let mut parent = ObjectBody::empty();
+        parent.set_member_str("x", Value::Num(0.0));
+        parent.set_member_str("y", Value::Num(0.0));
+        parent.add_method_str("歩く", |this, _, _| Ok(this.clone()));
+        let parent_id = vm.allocate(parent).unwrap();
+        let mut child = ObjectBody::new(Some(parent_id));
+        child.set_member_str("x", Value::Num(1.0));
+        child.add_method_str("跳ぶ", |this, _, _| Ok(this.clone()));
+        let child_id = vm.allocate(child).unwrap();
+        let child = vm.get_object(child_id).unwrap();
+
+        assert_eq!(child.own_members(), vec![(intern("x"), Value::Num(1.0))]);
+        assert_eq!(child.members(&vm), vec![
+            ("x".to_string(), Value::Num(1.0)),
+            ("y".to_string(), Value::Num(0.0)),
+        ]);
+        assert_eq!(child.method_names(&vm), vec!["跳ぶ".to_string(), "歩く".to_string()]);
+        assert_eq!(child.ancestors(&vm).iter().map(|o| o.id()).collect::<Vec<_>>(),
+                   vec![child_id, parent_id]);
     }
 }
 
 pub mod root {
     use crate::types::Value;
-    use crate::vm::VirtualMachine;
+    use crate::vm::{ObjectId, VirtualMachine};
     use crate::error::Result;
-    use crate::object::{Object, ObjectBody};
-    use std::sync::RwLock;
+    use crate::object::ObjectBody;
 
-    pub fn create(this: &Value, _args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let this_obj = vm.get_object_from_value(this)?;
-        let new_object = ObjectBody::new(&Some(this_obj.clone()));
+    pub fn create(this: &Value, _args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        let parent_id = vm.get_object_from_value(this)?.id();
+        let new_object = ObjectBody::new(Some(parent_id));
+        Ok(Value::ObjectReference(vm.allocate(new_object)?))
+    }
+
+    // Copies the receiver's own slots; inherited ones stay shared through the parent.
+    pub fn clone(this: &Value, _args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        let new_object = vm.get_object_from_value(this)?.body.clone();
         Ok(Value::ObjectReference(vm.allocate(new_object)?))
     }
+
+    fn is_block(value: &Value, vm: &VirtualMachine) -> bool {
+        let block_id = match vm.get_block_object_value().and_then(|v| v.as_object_id()) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+        value.as_object(vm).map(|obj| obj.is_subclass(block_id, vm)).unwrap_or(false)
+    }
+
+    fn new_array(names: Vec<String>, vm: &mut VirtualMachine) -> Result<Value> {
+        super::array::new(names.into_iter().map(Value::Str).collect(), vm)
+    }
+
+    // Native methods and members holding blocks, since both answer messages.
+    pub fn method_list(this: ObjectId, vm: &mut VirtualMachine) -> Result<Value> {
+        let this_obj = vm.get_object(this)?;
+        let mut names = this_obj.method_names(vm);
+        names.extend(this_obj.members(vm).into_iter()
+            .filter(|(name, v)| is_block(v, vm) && !names.contains(name))
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>());
+        new_array(names, vm)
+    }
+
+    pub fn variable_list(this: ObjectId, vm: &mut VirtualMachine) -> Result<Value> {
+        let names = vm.get_object(this)?.members(vm).into_iter()
+            .filter(|(_, v)| !is_block(v, vm))
+            .map(|(name, _)| name)
+            .collect();
+        new_array(names, vm)
+    }
+
+    pub fn parent(this: ObjectId, vm: &mut VirtualMachine) -> Result<Option<ObjectId>> {
+        Ok(vm.get_object(this)?.parent())
+    }
+
+    pub fn call_super(this: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        vm.call_super(this, args)
+    }
+
+    pub fn mixin(this: ObjectId, mixin: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.add_mixin(this, mixin)?;
+        Ok(this)
+    }
+
+    pub fn is_a(this: ObjectId, prototype: ObjectId, vm: &mut VirtualMachine) -> Result<bool> {
+        Ok(this == prototype || vm.get_object(this)?.is_subclass(prototype, vm))
+    }
 }
 
+#[cfg(feature = "turtle")]
 pub mod turtle {
     use crate::types::Value;
-    use crate::vm::VirtualMachine;
-    use crate::error::{Error, Result};
-    use utilities::geometry::dir_vector;
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::error::Result;
+    use crate::error::Error;
+    use crate::convert::positive;
+    use crate::gc::Trace;
+    use crate::symbol::intern;
+    use crate::animation::Motion;
+    use utilities::geometry::{dir_vector, direction_to, distance, point_on_circle, quadratic_to_cubic, Vector2D};
+    use std::sync::Arc;
 
     const x: &str = "x";
     const y: &str = "y";
     const direction: &str = "direction";
     const visible: &str = "visible";
 
-    pub fn create(this: &Value, _args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
+    // The lines drawn since the turtle last lifted its pen, jumped or made a
+    // shape, and the corners they pass through. Steps walked in the same pen
+    // extend `trail` instead of drawing a new line.
+    #[derive(Clone, Default)]
+    pub struct Path {
+        pub points: Vec<(f64, f64)>,
+        pub lines: Vec<ObjectId>,
+        pub trail: Option<ObjectId>,
+    }
+
+    impl Trace for Path {
+        fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+            self.lines.trace(mark);
+            self.trail.trace(mark);
+        }
+    }
+
+    fn path_mut(this: ObjectId, vm: &mut VirtualMachine) -> Result<&mut Path> {
+        let this_obj = vm.get_object_mut(this)?;
+        if this_obj.internal_value::<Path>().is_none() {
+            this_obj.set_internal_value(Arc::new(Path::default()));
+        }
+        this_obj.internal_value_mut::<Path>().ok_or(Error::Runtime)
+    }
+
+    fn set_path(this: ObjectId, path: Path, vm: &mut VirtualMachine) -> Result<()> {
+        vm.get_object_mut(this)?.set_internal_value(Arc::new(path));
+        Ok(())
+    }
+
+    pub fn create(this: &Value, _args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
         let obj_value: Value = super::root::create(this, _args, vm)?;
-        let obj = vm.get_object_from_value(&obj_value)?;
-        obj.set_member_str(visible, Value::Bool(true), vm);
+        let obj = vm.get_object_mut_from_value(&obj_value)?;
+        obj.set_member_str(visible, Value::Bool(true));
+        vm.pin_object(obj_value.as_object_id()?);
         Ok(obj_value)
     }
 
-    pub fn walk(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let amount = args.get(0).ok_or(Error::ArgumentError)?.as_num()?;
-        let this_obj = vm.get_object_from_value(this)?;
-        let dv = dir_vector(this_obj.get_member_str(direction, vm)?.as_num()?);
-        let (x1, y1) =
-            (this_obj.get_member_str(x, vm)?.as_num()?, this_obj.get_member_str(y, vm)?.as_num()?);
-        let (x2, y2) = (x1 + amount * dv.x, y1 + amount * dv.y);
-        this_obj.set_member_str(x, Value::Num(x2), vm);
-        this_obj.set_member_str(y, Value::Num(y2), vm);
-
-        let line = vm.call_method(
-            &Value::ObjectReference(vm.get_object_id_in_assigns(
-                vm.to_symbol("線"))?),
-            vm.to_symbol("作る"), &vec![])?;
-        let line_obj = vm.get_object_from_value(&line)?;
-        line_obj.set_member_str("x1", Value::Num(x1), vm);
-        line_obj.set_member_str("y1", Value::Num(y1), vm);
-        line_obj.set_member_str("x2", Value::Num(x2), vm);
-        line_obj.set_member_str("y2", Value::Num(y2), vm);
+    pub fn clone(this: &Value, _args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        let obj_value = super::root::clone(this, _args, vm)?;
+        set_path(obj_value.as_object_id()?, Path::default(), vm)?;
+        vm.pin_object(obj_value.as_object_id()?);
+        Ok(obj_value)
+    }
 
-        Ok(this.clone())
+    fn is_pen_down(this: ObjectId, vm: &VirtualMachine) -> Result<bool> {
+        vm.get_object(this)?.get_member_str("pen", vm)?.as_bool()
     }
 
-    pub fn turn_left(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let angle_deg = args.get(0).ok_or(Error::ArgumentError)?.as_num()?;
-        let this_obj = vm.get_object_from_value(this)?;
-        this_obj.set_member_str(direction,
-                                Value::Num(this_obj.get_member_str(direction, vm)?.as_num()? + angle_deg), vm);
+    fn heading(this: ObjectId, vm: &VirtualMachine) -> Result<f64> {
+        vm.get_object(this)?.get_member_str(direction, vm)?.as_num()
+    }
 
-        Ok(this.clone())
+    // Turning this many degrees takes as long as walking one step.
+    const DEGREES_PER_STEP: f64 = 3.0;
+
+    // Queues a motion for hosts that animate, taking as long as walking
+    // `steps` at the turtle's 速さ.
+    fn animate(this: ObjectId, motion: Motion, steps: f64, vm: &mut VirtualMachine) -> Result<()> {
+        if vm.animation().is_enabled() {
+            let speed = vm.get_object(this)?.get_member_str("speed", vm)?.as_num()?;
+            vm.animation_mut().push(motion, steps.abs() / speed);
+        }
+        Ok(())
     }
 
-    pub fn turn_right(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let angle_deg = -(args.get(0).ok_or(Error::ArgumentError)?.as_num()?);
-        let this_obj = vm.get_object_from_value(this)?;
-        this_obj.set_member_str(direction,
-                                Value::Num(this_obj.get_member_str(direction, vm)?.as_num()? + angle_deg), vm);
+    fn animate_move(this: ObjectId, from: &Vector2D, to: &Vector2D, line: Option<ObjectId>, steps: f64, vm: &mut VirtualMachine) -> Result<()> {
+        animate(this, Motion::Move { turtle: this, from: (from.x, from.y), to: (to.x, to.y), line }, steps, vm)
+    }
 
-        Ok(this.clone())
+    fn animate_turn(this: ObjectId, from: f64, to: f64, vm: &mut VirtualMachine) -> Result<()> {
+        animate(this, Motion::Turn { turtle: this, from, to }, (to - from) / DEGREES_PER_STEP, vm)
+    }
+
+    fn set_position(this: ObjectId, to: &Vector2D, vm: &mut VirtualMachine) -> Result<()> {
+        let this_obj = vm.get_object_mut(this)?;
+        this_obj.set_member_str(x, Value::Num(to.x));
+        this_obj.set_member_str(y, Value::Num(to.y));
+        Ok(())
+    }
+
+    // Creates a 線, 円, 弧 or 曲線 in the turtle's current pen and keeps it
+    // on screen.
+    fn draw(this: ObjectId, prototype: &str, members: &[(&str, f64)], vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let this_obj = vm.get_object(this)?;
+        let color = this_obj.get_member_str("line_color", vm)?;
+        let width = this_obj.get_member_str("line_width", vm)?;
+        let prototype = Value::ObjectReference(vm.get_object_id_in_assigns(intern(prototype))?);
+        let stroke = vm.call_method(&prototype, intern("作る"), &vec![])?.as_object_id()?;
+        let stroke_obj = vm.get_object_mut(stroke)?;
+        for (name, value) in members.iter() {
+            stroke_obj.set_member_str(name, Value::Num(*value));
+        }
+        stroke_obj.set_member_str("color", color);
+        stroke_obj.set_member_str("width", width);
+        stroke_obj.set_member_str("owner", Value::ObjectReference(this));
+        vm.pin_object(stroke);
+        Ok(stroke)
+    }
+
+    pub fn walk(this: ObjectId, amount: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let from = position_of(this, vm)?;
+        let dv = dir_vector(heading(this, vm)?);
+        let to = Vector2D { x: from.x + amount * dv.x, y: from.y + amount * dv.y };
+        set_position(this, &to, vm)?;
+        if !is_pen_down(this, vm)? {
+            set_path(this, Path::default(), vm)?;
+            animate_move(this, &from, &to, None, amount, vm)?;
+            crate::collision::detect(vm)?;
+            return Ok(this);
+        }
+
+        // x and y assigned directly move the turtle without drawing
+        if path_mut(this, vm)?.points.last().is_some_and(|p| *p != (from.x, from.y)) {
+            set_path(this, Path::default(), vm)?;
+        }
+        let trail = path_mut(this, vm)?.trail;
+        let line = if let Some(trail) = trail {
+            super::line::extend(trail, (to.x, to.y), vm)?;
+            trail
+        } else {
+            let line = draw(this, "線", &[("x1", from.x), ("y1", from.y), ("x2", to.x), ("y2", to.y)], vm)?;
+            vm.get_object_mut(line)?.set_internal_value(Arc::new(vec![(from.x, from.y), (to.x, to.y)]));
+            let path = path_mut(this, vm)?;
+            path.lines.push(line);
+            path.trail = Some(line);
+            line
+        };
+        let path = path_mut(this, vm)?;
+        if path.points.is_empty() {
+            path.points.push((from.x, from.y));
+        }
+        path.points.push((to.x, to.y));
+        animate_move(this, &from, &to, Some(line), amount, vm)?;
+        crate::collision::detect(vm)?;
+
+        Ok(this)
+    }
+
+    // Centred on the turtle, which stays where it is.
+    pub fn circle(this: ObjectId, radius: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let radius = positive(radius)?;
+        if is_pen_down(this, vm)? {
+            let center = position_of(this, vm)?;
+            let circle = draw(this, "円", &[("cx", center.x), ("cy", center.y), ("r", radius)], vm)?;
+            animate(this, Motion::Draw { object: circle }, 2.0 * std::f64::consts::PI * radius, vm)?;
+        }
+        Ok(this)
+    }
+
+    // Walks along a circle of `radius`, turning left by `angle_deg` on the
+    // way, or right when it is negative.
+    pub fn arc(this: ObjectId, radius: f64, angle_deg: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let radius = positive(radius)?;
+        let side = if angle_deg < 0.0 { -1.0 } else { 1.0 };
+        let heading_deg = heading(this, vm)?;
+        let center = point_on_circle(&position_of(this, vm)?, radius, heading_deg + 90.0 * side);
+        let start = heading_deg - 90.0 * side;
+        let end = start + angle_deg;
+        let from = position_of(this, vm)?;
+        let to = point_on_circle(&center, radius, end);
+
+        set_position(this, &to, vm)?;
+        vm.get_object_mut(this)?.set_member_str(direction, Value::Num(heading_deg + angle_deg));
+        set_path(this, Path::default(), vm)?;
+        let mut length = radius * angle_deg.to_radians();
+        if is_pen_down(this, vm)? {
+            let arc = draw(this, "弧", &[("cx", center.x), ("cy", center.y), ("r", radius), ("start", start), ("end", end)], vm)?;
+            animate(this, Motion::Draw { object: arc }, length, vm)?;
+            length = 0.0;
+        }
+        animate_move(this, &from, &to, None, length, vm)?;
+        animate(this, Motion::Turn { turtle: this, from: heading_deg, to: heading_deg + angle_deg }, 0.0, vm)?;
+        crate::collision::detect(vm)?;
+        Ok(this)
+    }
+
+    // Takes the end point after one control point, or after two for a cubic
+    // curve, and leaves the turtle facing along the curve.
+    pub fn curve(this: ObjectId, coordinates: Vec<f64>, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let start = position_of(this, vm)?;
+        let point = |i: usize| Vector2D { x: coordinates[i], y: coordinates[i + 1] };
+        let controls = match coordinates.len() {
+            4 => quadratic_to_cubic(&start, &point(0), &point(2)),
+            6 => [start, point(0), point(2), point(4)],
+            _ => return Err(Error::ArgumentError),
+        };
+
+        let heading_deg = heading(this, vm)?;
+        let end_heading = direction_to(&controls[2], &controls[3]);
+        set_position(this, &controls[3], vm)?;
+        vm.get_object_mut(this)?.set_member_str(direction, Value::Num(end_heading));
+        set_path(this, Path::default(), vm)?;
+        let mut length = distance(&start, &controls[3]);
+        if is_pen_down(this, vm)? {
+            let [p0, p1, p2, p3] = controls;
+            let curve = draw(this, "曲線", &[
+                ("x1", p0.x), ("y1", p0.y), ("cx1", p1.x), ("cy1", p1.y),
+                ("cx2", p2.x), ("cy2", p2.y), ("x2", p3.x), ("y2", p3.y),
+            ], vm)?;
+            animate(this, Motion::Draw { object: curve }, length, vm)?;
+            length = 0.0;
+        }
+        animate_move(this, &start, &controls[3], None, length, vm)?;
+        animate(this, Motion::Turn { turtle: this, from: heading_deg, to: end_heading }, 0.0, vm)?;
+        crate::collision::detect(vm)?;
+        Ok(this)
+    }
+
+    // Replaces the lines of the current path with a 図形 outlined the same way.
+    pub fn make_shape(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        if path_mut(this, vm)?.points.len() < 3 {
+            return Err(Error::ArgumentError);
+        }
+        let this_obj = vm.get_object(this)?;
+        let color = this_obj.get_member_str("line_color", vm)?;
+        let width = this_obj.get_member_str("line_width", vm)?;
+        let path = std::mem::take(path_mut(this, vm)?);
+        let shape = super::shape::create(&path.points, color, width, vm)?;
+
+        for line in path.lines.iter() {
+            vm.get_object_mut(*line)?.set_member_str(visible, Value::Bool(false));
+            vm.unpin_object(*line);
+        }
+        vm.get_object_mut(shape)?.set_member_str("owner", Value::ObjectReference(this));
+        animate(this, Motion::Draw { object: shape }, 0.0, vm)?;
+        Ok(shape)
+    }
+
+    pub fn pen_up(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.get_object_mut(this)?.set_member_str("pen", Value::Bool(false));
+        Ok(this)
+    }
+
+    pub fn pen_down(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.get_object_mut(this)?.set_member_str("pen", Value::Bool(true));
+        Ok(this)
+    }
+
+    pub fn set_line_color(this: ObjectId, color: Value, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let color = super::color::hex_of(&color, vm)?;
+        vm.get_object_mut(this)?.set_member_str("line_color", color);
+        path_mut(this, vm)?.trail = None;
+        Ok(this)
+    }
+
+    pub fn set_line_width(this: ObjectId, width: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let width = positive(width)?;
+        vm.get_object_mut(this)?.set_member_str("line_width", Value::Num(width));
+        path_mut(this, vm)?.trail = None;
+        Ok(this)
+    }
+
+    pub fn hide(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.get_object_mut(this)?.set_member_str(visible, Value::Bool(false));
+        Ok(this)
+    }
+
+    pub fn show(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.get_object_mut(this)?.set_member_str(visible, Value::Bool(true));
+        Ok(this)
+    }
+
+    // `image` names a file in the front-end's assets, e.g. "ayumi.png".
+    // Paths are refused so a program cannot reach files outside them.
+    pub fn change_image(this: ObjectId, image: String, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        if image.is_empty() || image.contains(&['/', '\\'][..]) || image.contains("..") {
+            return Err(Error::ArgumentError);
+        }
+        vm.get_object_mut(this)?.set_member_str("image", Value::Str(image));
+        Ok(this)
+    }
+
+    pub fn set_scale(this: ObjectId, scale: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let scale = positive(scale)?;
+        vm.get_object_mut(this)?.set_member_str("scale", Value::Num(scale));
+        Ok(this)
+    }
+
+    // Steps per second when the host animates turtles.
+    pub fn set_speed(this: ObjectId, speed: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let speed = positive(speed)?;
+        vm.get_object_mut(this)?.set_member_str("speed", Value::Num(speed));
+        Ok(this)
+    }
+
+    pub fn turn_left(this: ObjectId, angle_deg: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let current = vm.get_object(this)?.get_member_str(direction, vm)?.as_num()?;
+        vm.get_object_mut(this)?
+            .set_member_str(direction, Value::Num(current + angle_deg));
+        animate_turn(this, current, current + angle_deg, vm)?;
+
+        Ok(this)
+    }
+
+    pub fn turn_right(this: ObjectId, angle_deg: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        turn_left(this, -angle_deg, vm)
+    }
+
+    pub fn walk_back(this: ObjectId, amount: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        walk(this, -amount, vm)
+    }
+
+    fn position_of(obj: ObjectId, vm: &VirtualMachine) -> Result<Vector2D> {
+        let obj = vm.get_object(obj)?;
+        Ok(Vector2D {
+            x: obj.get_member_str(x, vm)?.as_num()?,
+            y: obj.get_member_str(y, vm)?.as_num()?,
+        })
+    }
+
+    // Jumps without drawing a line, whatever the pen state.
+    pub fn move_to(this: ObjectId, to_x: f64, to_y: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let from = position_of(this, vm)?;
+        let to = Vector2D { x: to_x, y: to_y };
+        set_position(this, &to, vm)?;
+        set_path(this, Path::default(), vm)?;
+        animate_move(this, &from, &to, None, 0.0, vm)?;
+        crate::collision::detect(vm)?;
+        Ok(this)
+    }
+
+    pub fn position(this: ObjectId, vm: &mut VirtualMachine) -> Result<Value> {
+        let p = position_of(this, vm)?;
+        super::array::new(vec![Value::Num(p.x), Value::Num(p.y)], vm)
+    }
+
+    pub fn set_direction(this: ObjectId, angle_deg: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let current = heading(this, vm)?;
+        vm.get_object_mut(this)?.set_member_str(direction, Value::Num(angle_deg));
+        animate_turn(this, current, angle_deg, vm)?;
+        Ok(this)
+    }
+
+    pub fn home(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        move_to(this, 0.0, 0.0, vm)?;
+        set_direction(this, 0.0, vm)
+    }
+
+    pub fn distance_to(this: ObjectId, other: ObjectId, vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(distance(&position_of(this, vm)?, &position_of(other, vm)?))
+    }
+
+    pub fn face(this: ObjectId, other: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let angle_deg = direction_to(&position_of(this, vm)?, &position_of(other, vm)?);
+        set_direction(this, angle_deg, vm)
+    }
+}
+
+#[cfg(feature = "turtle")]
+pub mod line {
+    use crate::types::Value;
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::error::{Error, Result};
+    use crate::object::Object;
+
+    // A turtle's line keeps every corner it passes through; x1/y1 and x2/y2
+    // are its two ends.
+    type Points = Vec<(f64, f64)>;
+
+    pub fn extend(this: ObjectId, to: (f64, f64), vm: &mut VirtualMachine) -> Result<()> {
+        let this_obj = vm.get_object_mut(this)?;
+        this_obj.internal_value_mut::<Points>().ok_or(Error::Runtime)?.push(to);
+        this_obj.set_member_str("x2", Value::Num(to.0));
+        this_obj.set_member_str("y2", Value::Num(to.1));
+        Ok(())
+    }
+
+    // The corners in screen coordinates, for the view models. A 線 made by
+    // hand only has its ends.
+    pub fn points(line: &Object, vm: &VirtualMachine) -> Result<Vec<(f64, f64)>> {
+        if let Some(points) = line.internal_value::<Points>() {
+            return Ok((*points).clone());
+        }
+        let num = |name: &str| line.get_member_str(name, vm)?.as_num();
+        Ok(vec![(num("x1")?, num("y1")?), (num("x2")?, num("y2")?)])
+    }
+}
+
+#[cfg(feature = "turtle")]
+pub mod shape {
+    use crate::types::Value;
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::error::{Error, Result};
+    use crate::object::Object;
+    use utilities::geometry::{centroid, distance, rotate, Vector2D};
+    use std::sync::Arc;
+
+    // The internal value holds the corners relative to x/y before turning by
+    // direction, so moving and rotating only touch members.
+    type Vertices = Vec<(f64, f64)>;
+
+    pub fn create(points: &[(f64, f64)], color: Value, width: Value, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let mut points: Vec<Vector2D> = points.iter().map(|(px, py)| Vector2D { x: *px, y: *py }).collect();
+        if points.len() > 2 && distance(&points[0], &points[points.len() - 1]) < 1e-9 {
+            points.pop();
+        }
+        let center = centroid(&points);
+        let vertices: Vertices = points.iter().map(|p| (p.x - center.x, p.y - center.y)).collect();
+
+        let prototype = vm.get_value_in_scope_from_symbol("図形")?;
+        let shape = super::root::create(&prototype, &vec![], vm)?.as_object_id()?;
+        let shape_obj = vm.get_object_mut(shape)?;
+        shape_obj.set_member_str("x", Value::Num(center.x));
+        shape_obj.set_member_str("y", Value::Num(center.y));
+        shape_obj.set_member_str("color", color);
+        shape_obj.set_member_str("width", width);
+        shape_obj.set_internal_value(Arc::new(vertices));
+        vm.pin_object(shape);
+        Ok(shape)
+    }
+
+    // Without a colour the shape is filled with its outline colour.
+    pub fn fill(this: ObjectId, color: Option<Value>, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let color = match color {
+            Some(color) => super::color::hex_of(&color, vm)?,
+            None => vm.get_object(this)?.get_member_str("color", vm)?,
+        };
+        vm.get_object_mut(this)?.set_member_str("fill", color);
+        Ok(this)
+    }
+
+    pub fn move_to(this: ObjectId, to_x: f64, to_y: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let this_obj = vm.get_object_mut(this)?;
+        this_obj.set_member_str("x", Value::Num(to_x));
+        this_obj.set_member_str("y", Value::Num(to_y));
+        crate::collision::detect(vm)?;
+        Ok(this)
+    }
+
+    pub fn rotate_by(this: ObjectId, angle_deg: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let current = vm.get_object(this)?.get_member_str("direction", vm)?.as_num()?;
+        vm.get_object_mut(this)?.set_member_str("direction", Value::Num(current + angle_deg));
+        crate::collision::detect(vm)?;
+        Ok(this)
+    }
+
+    // The corners in screen coordinates, for the view models.
+    pub fn points(shape: &Object, vm: &VirtualMachine) -> Result<Vec<(f64, f64)>> {
+        let vertices = shape.internal_value::<Vertices>().ok_or(Error::Runtime)?;
+        let (cx, cy) = (shape.get_member_str("x", vm)?.as_num()?, shape.get_member_str("y", vm)?.as_num()?);
+        let angle_deg = shape.get_member_str("direction", vm)?.as_num()?;
+        Ok(vertices.iter()
+            .map(|(vx, vy)| rotate(&Vector2D { x: *vx, y: *vy }, angle_deg))
+            .map(|v| (cx + v.x, cy + v.y))
+            .collect())
+    }
+}
+
+#[cfg(feature = "color")]
+pub mod color {
+    use crate::types::Value;
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::error::{Error, Result};
+    use crate::gc::Trace;
+    use crate::symbol::intern;
+    use utilities::color::Rgb;
+    use std::sync::Arc;
+
+    impl Trace for Rgb {
+        fn trace(&self, _mark: &mut dyn FnMut(ObjectId)) {}
+    }
+
+    // Every colour delegates to 色 itself, whichever colour it was made from.
+    pub fn create_value(rgb: Rgb, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let prototype = Value::ObjectReference(vm.get_object_id_in_assigns(intern("色"))?);
+        let color = super::root::create(&prototype, &vec![], vm)?.as_object_id()?;
+        vm.get_object_mut(color)?.set_internal_value(Arc::new(rgb));
+        Ok(color)
+    }
+
+    // Accepts 色 objects from programs and `#rrggbb` strings from hosts.
+    pub fn rgb_of(value: &Value, vm: &VirtualMachine) -> Result<Rgb> {
+        match value {
+            Value::Str(hex) => Rgb::from_hex(hex).ok_or(Error::ArgumentError),
+            Value::ObjectReference(id) => vm.get_object(*id)?.internal_value::<Rgb>()
+                .map(|rgb| *rgb)
+                .ok_or(Error::ArgumentError),
+            _ => Err(Error::ArgumentError),
+        }
+    }
+
+    // Drawn objects keep colours as `#rrggbb` so both front-ends can use
+    // them as is.
+    pub fn hex_of(value: &Value, vm: &VirtualMachine) -> Result<Value> {
+        Ok(Value::Str(rgb_of(value, vm)?.to_hex()))
+    }
+
+    fn channel(value: f64) -> u8 {
+        value.clamp(0.0, 255.0).round() as u8
+    }
+
+    // Each channel from 0 to 255.
+    pub fn create(_this: ObjectId, r: f64, g: f64, b: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        create_value(Rgb::new(channel(r), channel(g), channel(b)), vm)
+    }
+
+    // Hue in degrees, saturation and value from 0 to 100.
+    pub fn create_hsv(_this: ObjectId, h: f64, s: f64, v: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        create_value(Rgb::from_hsv(h, s / 100.0, v / 100.0), vm)
+    }
+
+    // Named apart from ルート's 混ぜる, which still mixes prototypes into
+    // colours.
+    pub fn mix(this: ObjectId, others: Vec<Value>, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let mut colors = vec![rgb_of(&Value::ObjectReference(this), vm)?];
+        for other in others.iter() {
+            colors.push(rgb_of(other, vm)?);
+        }
+        create_value(Rgb::mix(&colors).ok_or(Error::ArgumentError)?, vm)
+    }
+
+    pub fn random(_this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let mut channel = || (vm.random() * 256.0) as u8;
+        let rgb = Rgb::new(channel(), channel(), channel());
+        create_value(rgb, vm)
+    }
+}
+
+#[cfg(feature = "turtle")]
+pub mod screen {
+    use crate::types::Value;
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::error::Result;
+
+    pub fn set_background(this: ObjectId, color: Value, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let color = super::color::hex_of(&color, vm)?;
+        vm.get_object_mut(this)?.set_member_str("background", color);
+        Ok(this)
     }
 }
 
 pub mod block {
     use crate::types::Value;
-    use crate::vm::VirtualMachine;
+    use crate::vm::{Evaluator, VirtualMachine};
     use crate::error::{Error, Result};
-    use crate::ast::{ASTNode};
-    use std::borrow::Borrow;
+    use crate::ast::{ASTNode, Eval};
+    use crate::bytecode::{self, BlockCode};
+    use crate::symbol::{intern, SymbolId};
     use std::sync::Arc;
 
-    type BlockInternalValue = (Vec<String>, Vec<Arc<ASTNode>>);
+    pub fn create(this: &Value, dummy_args: &Vec<SymbolId>,
+                  body: &Vec<Arc<ASTNode>>, vm: &mut VirtualMachine) -> Result<Value> {
+        create_from_code(this, Arc::new(BlockCode::new(dummy_args, body)), vm)
+    }
 
-    pub fn create(this: &Value, dummy_args: &Vec<String>,
-                  body: &Vec<Arc<ASTNode>>, vm: &VirtualMachine) -> Result<Value> {
+    pub fn create_from_code(this: &Value, code: Arc<BlockCode>, vm: &mut VirtualMachine) -> Result<Value> {
         let obj_value: Value = super::root::create(this, &vec![], vm)?;
-        let obj: Arc<super::Object> = vm.get_object_from_value(&obj_value)?;
-        let v = Arc::new(
-            (dummy_args.clone(),
-             body.clone()
-            ));
-        obj.set_internal_value(v);
+        vm.get_object_mut_from_value(&obj_value)?.set_internal_value(code);
         Ok(obj_value)
     }
 
-    pub fn empty_block(vm: &VirtualMachine) -> Result<Value> {
+    pub fn empty_block(vm: &mut VirtualMachine) -> Result<Value> {
         let block = vm.get_value_in_scope_from_symbol("ブロック").expect("not defined ブロック");
-        let obj_value: Value = super::root::create(&block, &vec![], vm)?;
-        let obj: Arc<super::Object> = vm.get_object_from_value(&obj_value)?;
-        let v: Arc<(Vec<String>, Vec<Arc<ASTNode>>)> = Arc::new(
-            (vec![],
-             vec![],
-            ));
-        obj.set_internal_value(v);
-        Ok(obj_value)
+        create(&block, &vec![], &vec![], vm)
     }
 
-    pub fn repeat(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let n: f64 = args.first().ok_or(Error::Runtime)?.as_num()?;
+    pub fn repeat(this: Value, n: f64, vm: &mut VirtualMachine) -> Result<Value> {
         if n.is_sign_negative() {
             return Err(Error::Runtime);
         }
@@ -310,31 +1020,44 @@ pub mod block {
         }
 
         for _ in 0..n-1 {
-            exec(this, &vec![], vm)?;
+            vm.step()?;
+            exec(&this, &vec![], vm)?;
+            vm.collect_garbage_if_needed();
         }
-        exec(this, &vec![], vm)
+        vm.step()?;
+        exec(&this, &vec![], vm)
     }
 
-    pub fn if_(this: &Value, _args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let object_id = super::condition::create_internal(vm)?;
-        let object = vm.get_object(object_id).unwrap();
+    pub fn if_(this: &Value, _args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
         let flag = exec(this, &vec![], vm)?.as_bool()?;
-        object.set_member_str("flag", Value::Bool(flag), vm);
+        let object_id = super::condition::create_internal(vm)?;
+        vm.get_object_mut(object_id)?.set_member_str("flag", Value::Bool(flag));
 
         Ok(Value::ObjectReference(object_id))
     }
 
-    pub fn exec(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let this_obj = vm.get_object_from_value(this)?;
-        let t = this_obj.get_internal_value::<BlockInternalValue>();
-        let (dummy_args, body) = t.borrow();
-        vm.push_stack(dummy_args, args);
-        let mut result = Value::Null;
-        for b in body.iter() {
-            result = vm.eval(b)?;
+    pub fn exec(this: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        run(this, None, args, vm)
+    }
+
+    // Runs the block as a method of `receiver`, which the body sees as 自分.
+    pub fn exec_method(this: &Value, receiver: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        run(this, Some(receiver), args, vm)
+    }
+
+    fn run(this: &Value, receiver: Option<&Value>, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        let block = vm.get_object_from_value(this)?.get_internal_value::<BlockCode>();
+        vm.push_stack(&block.dummy_args, args)?;
+        if let Some(receiver) = receiver {
+            vm.bind_local(intern("自分"), receiver.clone());
         }
+        let result = match vm.evaluator() {
+            Evaluator::TreeWalker => block.body.iter()
+                .try_fold(Value::Null, |_, b| b.eval(vm)),
+            Evaluator::Bytecode => bytecode::run(block.chunk(), vm),
+        };
         vm.pop_stack();
-        return Ok(result)
+        result
     }
 }
 
@@ -342,28 +1065,23 @@ pub mod condition {
     use crate::vm::{VirtualMachine, ObjectId};
     use crate::types::Value;
     use crate::error::{Result};
-    use crate::object::Object;
     use crate::object::root::create;
-    use std::sync::Arc;
-
-    pub fn create_super_object(root_object_id: ObjectId, vm: &VirtualMachine) -> Result<ObjectId> {
-        let root_value = Value::ObjectReference(root_object_id);
-        let super_object_value: Value = super::root::create(&root_value, &vec![], vm)?;
-        let super_object: Arc<Object> = super_object_value.as_object(vm)?;
-
-        super_object.set_member_str("flag", Value::Bool(false), vm);
-        super_object.add_method_str("実行", exec, vm);
-        super_object.add_method_str("そうでないなら", else_, vm);
-        let _ = vm.assign(vm.to_symbol("Condition"), &super_object_value);
-        super_object_value.as_object_id()
+    use crate::prototype::PrototypeBuilder;
+
+    pub fn create_super_object(vm: &mut VirtualMachine) -> Result<ObjectId> {
+        PrototypeBuilder::new("Condition")
+            .member("flag", Value::Bool(false))
+            .method("実行", exec)
+            .method("そうでないなら", else_)
+            .install(vm)
     }
 
-    pub fn create_internal(vm: &VirtualMachine) -> Result<ObjectId> {
+    pub fn create_internal(vm: &mut VirtualMachine) -> Result<ObjectId> {
         let v = vm.get_value_in_scope_from_symbol("Condition")?;
         create(&v, &vec![], vm)?.as_object_id()
     }
 
-    pub fn exec(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
+    pub fn exec(this: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
         assert_eq!(args.len(), 1);
 
         let this_obj = vm.get_object_from_value(this)?;
@@ -375,43 +1093,167 @@ pub mod condition {
         Ok(this.clone())
     }
 
-    pub fn else_(this: &Value, args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
+    pub fn else_(this: &Value, args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
         assert_eq!(args.len(), 0);
 
         let this_obj = vm.get_object_from_value(this)?;
         let b = this_obj.get_member_str("flag", vm)?.as_bool()?;
 
         let object_id = create_internal(vm)?;
-        let object = vm.get_object(object_id).unwrap();
-        object.set_member_str("flag", Value::Bool(!b), vm);
+        vm.get_object_mut(object_id)?.set_member_str("flag", Value::Bool(!b));
 
         Ok(Value::ObjectReference(object_id))
     }
 }
 
+#[cfg(feature = "gui")]
 pub mod button {
     use crate::vm::{ObjectId, VirtualMachine};
     use crate::types::Value;
-    use std::sync::Arc;
-    use crate::object::Object;
     use crate::error::Result;
+    use crate::prototype::PrototypeBuilder;
+
+    pub fn create_super_object(vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let dousa = super::block::empty_block(vm)?;
+        PrototypeBuilder::new("ボタン")
+            .member("動作", dousa)
+            .method("クリック", click)
+            .install(vm)
+    }
+
+    pub fn click(this: &Value, _args: &Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        let dousa = this.as_object(vm)?.get_member_str("動作", vm).expect("not defined 動作");
 
-    pub fn create_super_object(root_object_id: ObjectId, vm: &VirtualMachine) -> Result<ObjectId> {
-        let root_value = Value::ObjectReference(root_object_id);
-        let super_object_value: Value = super::root::create(&root_value, &vec![], vm)?;
-        let super_object: Arc<Object> = super_object_value.as_object(vm)?;
+        super::block::exec(&dousa, &vec![], vm)
+    }
+}
+
+pub mod timer {
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::types::Value;
+    use crate::error::{Error, Result};
+    use crate::convert::positive;
+    use crate::bytecode::BlockCode;
 
-        super_object.set_member_str("動作", super::block::empty_block(vm)?, vm);
-        super_object.add_method_str("クリック", click, vm);
+    pub fn set_interval(this: ObjectId, seconds: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let seconds = positive(seconds)?;
+        vm.get_object_mut(this)?.set_member_str("interval", Value::Num(seconds));
+        Ok(this)
+    }
 
-        let _ = vm.assign(vm.to_symbol("ボタン"), &super_object_value);
-        super_object_value.as_object_id()
+    pub fn set_times(this: ObjectId, times: f64, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let times = positive(times.floor())?;
+        vm.get_object_mut(this)?.set_member_str("times", Value::Num(times));
+        Ok(this)
     }
 
-    pub fn click(this: &Value, _args: &Vec<Value>, vm: &VirtualMachine) -> Result<Value> {
-        let this_object: Arc<Object> = this.as_object(vm)?;
-        let dousa = this_object.get_member_str("動作", vm).expect("not defined 動作");
+    // The block runs at the host's ticks, given how many times it has run
+    // including this one.
+    pub fn start(this: ObjectId, block: Value, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.get_object_from_value(&block)?.internal_value::<BlockCode>().ok_or(Error::ArgumentError)?;
+        let this_obj = vm.get_object(this)?;
+        let interval = this_obj.get_member_str("interval", vm)?.as_num()?;
+        let times = this_obj.get_member_str("times", vm)?.as_num()?;
+        vm.scheduler_mut().start(this, block, interval, times as u64);
+        Ok(this)
+    }
 
-        super::block::exec(&dousa, &vec![], vm)
+    pub fn stop(this: ObjectId, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        vm.scheduler_mut().stop(this);
+        Ok(this)
     }
-}
\ No newline at end of file
+}
+
+pub mod math {
+    use crate::types::Value;
+    use crate::vm::VirtualMachine;
+    use crate::error::{Error, Result};
+
+    pub fn sqrt(_this: Value, n: f64, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(n.sqrt())
+    }
+
+    pub fn abs(_this: Value, n: f64, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(n.abs())
+    }
+
+    pub fn floor(_this: Value, n: f64, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(n.floor())
+    }
+
+    pub fn round(_this: Value, n: f64, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(n.round())
+    }
+
+    // angles are in degrees, like the turtle's
+    pub fn sin(_this: Value, deg: f64, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(deg.to_radians().sin())
+    }
+
+    pub fn cos(_this: Value, deg: f64, _vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(deg.to_radians().cos())
+    }
+
+    pub fn max(_this: Value, ns: Vec<f64>, _vm: &mut VirtualMachine) -> Result<f64> {
+        ns.into_iter().fold(None, |m: Option<f64>, n| Some(m.map_or(n, |m| m.max(n))))
+            .ok_or(Error::ArgumentError)
+    }
+
+    pub fn min(_this: Value, ns: Vec<f64>, _vm: &mut VirtualMachine) -> Result<f64> {
+        ns.into_iter().fold(None, |m: Option<f64>, n| Some(m.map_or(n, |m| m.min(n))))
+            .ok_or(Error::ArgumentError)
+    }
+}
+
+pub mod array {
+    use crate::symbol::intern;
+    use crate::types::Value;
+    use crate::vm::{ObjectId, VirtualMachine};
+    use crate::error::{Error, Result};
+    use std::sync::Arc;
+
+    fn items(this: ObjectId, vm: &VirtualMachine) -> Result<Arc<Vec<Value>>> {
+        Ok(vm.get_object(this)?.get_internal_value::<Vec<Value>>())
+    }
+
+    pub fn create(this: ObjectId, elements: Vec<Value>, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let array = super::root::create(&Value::ObjectReference(this), &vec![], vm)?.as_object_id()?;
+        vm.get_object_mut(array)?.set_internal_value(Arc::new(elements));
+        Ok(array)
+    }
+
+    // For natives returning several values. 配列 is part of the core
+    // module, so this works in any VM.
+    pub fn new(elements: Vec<Value>, vm: &mut VirtualMachine) -> Result<Value> {
+        let prototype = vm.get_object_id_in_assigns(intern("配列"))?;
+        create(prototype, elements, vm).map(Value::ObjectReference)
+    }
+
+    pub fn push(this: ObjectId, value: Value, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let mut elements = (*items(this, vm)?).clone();
+        elements.push(value);
+        vm.get_object_mut(this)?.set_internal_value(Arc::new(elements));
+        Ok(this)
+    }
+
+    // indices start at 1
+    pub fn get(this: ObjectId, index: f64, vm: &mut VirtualMachine) -> Result<Value> {
+        let elements = items(this, vm)?;
+        if index < 1.0 {
+            return Err(Error::ArgumentError);
+        }
+        elements.get(index as usize - 1).cloned().ok_or(Error::ArgumentError)
+    }
+
+    pub fn len(this: ObjectId, vm: &mut VirtualMachine) -> Result<f64> {
+        Ok(items(this, vm)?.len() as f64)
+    }
+
+    pub fn for_each(this: ObjectId, block: Value, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        for element in items(this, vm)?.iter() {
+            vm.step()?;
+            super::block::exec(&block, &vec![element.clone()], vm)?;
+        }
+        Ok(this)
+    }
+}
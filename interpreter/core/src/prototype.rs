@@ -0,0 +1,126 @@
+use crate::error::Result;
+use crate::object::ObjectBody;
+use crate::symbol::intern;
+use crate::types::Value;
+use crate::vm::{ObjectId, VirtualMachine};
+
+/// Describes a built-in prototype so hosts can add objects such as ロボット
+/// without touching `VirtualMachine::initialize`.
+pub struct PrototypeBuilder {
+    name: String,
+    parent: String,
+    mixins: Vec<String>,
+    body: ObjectBody,
+}
+
+impl PrototypeBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            parent: "ルート".to_string(),
+            mixins: vec![],
+            body: ObjectBody::empty(),
+        }
+    }
+
+    pub fn parent(mut self, parent: &str) -> Self {
+        self.parent = parent.to_string();
+        self
+    }
+
+    pub fn mixin(mut self, mixin: &str) -> Self {
+        self.mixins.push(mixin.to_string());
+        self
+    }
+
+    pub fn member(mut self, name: &str, value: Value) -> Self {
+        self.body.set_member_str(name, value);
+        self
+    }
+
+    pub fn method<F>(mut self, name: &str, method: F) -> Self
+        where F: Fn(&Value, &Vec<Value>, &mut VirtualMachine) -> Result<Value> + Send + Sync + 'static {
+        self.body.add_method_str(name, method);
+        self
+    }
+
+    // The parent and mixins are looked up by name, so they have to be
+    // installed first.
+    pub fn install(self, vm: &mut VirtualMachine) -> Result<ObjectId> {
+        let parent_id = vm.get_object_id_in_assigns(intern(&self.parent))?;
+        let mut body = self.body;
+        body.set_parent(Some(parent_id));
+        for mixin in self.mixins.iter() {
+            body.add_parent(vm.get_object_id_in_assigns(intern(mixin))?);
+        }
+
+        let object_id = vm.allocate(body)?;
+        vm.assign(intern(&self.name), &Value::ObjectReference(object_id))?;
+        Ok(object_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::prototype::PrototypeBuilder;
+    use crate::types::Value;
+    use crate::vm::VirtualMachine;
+
+    #[test]
+    #[cfg(feature = "turtle")]
+    fn install_under_parent() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        let robot_id = PrototypeBuilder::new("ロボット")
+            .parent("タートル")
+            .member("電池", Value::Num(100.0))
+            .method("充電", |this, _args, vm| {
+                vm.get_object_mut_from_value(this)?.set_member_str("電池", Value::Num(100.0));
+                Ok(this.clone())
+            })
+            .install(&mut vm)
+            .unwrap();
+
+        let turtle_id = vm.get_object_in_assigns_from_symbol("タートル").unwrap().id();
+        let robot = vm.get_object_in_assigns_from_symbol("ロボット").unwrap();
+        assert_eq!(robot.id(), robot_id);
+        assert!(robot.is_subclass(turtle_id, &vm));
+        assert_eq!(robot.get_member_str("電池", &vm).unwrap(), Value::Num(100.0));
+        assert!(robot.get_method(vm.to_symbol("歩く"), &vm).is_ok());
+        assert!(robot.get_method(vm.to_symbol("充電"), &vm).is_ok());
+    }
+
+    #[test]
+    fn install_with_mixin() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        PrototypeBuilder::new("スイッチ")
+            .member("状態", Value::Bool(false))
+            .install(&mut vm)
+            .unwrap();
+        PrototypeBuilder::new("ロボット")
+            .parent("ルート")
+            .mixin("スイッチ")
+            .install(&mut vm)
+            .unwrap();
+
+        let root_id = vm.get_object_in_assigns_from_symbol("ルート").unwrap().id();
+        let switch_id = vm.get_object_in_assigns_from_symbol("スイッチ").unwrap().id();
+        let robot = vm.get_object_in_assigns_from_symbol("ロボット").unwrap();
+        assert_eq!(robot.parents(), &[root_id, switch_id]);
+        assert!(robot.is_subclass(switch_id, &vm));
+        assert_eq!(robot.get_member_str("状態", &vm).unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn install_without_parent_fails() {
+        let mut vm = VirtualMachine::new();
+        vm.initialize();
+
+        let result = PrototypeBuilder::new("センサー").parent("ロボット").install(&mut vm);
+        assert!(matches!(result, Err(Error::ObjectNotFound)));
+    }
+}
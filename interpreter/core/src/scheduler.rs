@@ -0,0 +1,109 @@
+use crate::gc::Trace;
+use crate::types::Value;
+use crate::vm::ObjectId;
+
+/// A started タイマー: runs `block` every `interval` seconds, `times` times.
+#[derive(Clone, Debug)]
+pub struct Timer {
+    pub timer: ObjectId,
+    pub block: Value,
+    pub interval: f64,
+    pub times: u64,
+    // runs so far
+    pub count: u64,
+    next_at: f64,
+}
+
+/// The timers of a VM, on a virtual clock that only moves when the host
+/// calls `tick`. Programs behave the same at any frame rate, and tests can
+/// step through time exactly.
+#[derive(Default)]
+pub struct Scheduler {
+    now: f64,
+    timers: Vec<Timer>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Seconds ticked since the VM was created.
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    // Starting a running timer again restarts it.
+    pub fn start(&mut self, timer: ObjectId, block: Value, interval: f64, times: u64) {
+        self.stop(timer);
+        if times > 0 {
+            self.timers.push(Timer { timer, block, interval, times, count: 0, next_at: self.now + interval });
+        }
+    }
+
+    pub fn stop(&mut self, timer: ObjectId) -> Option<Timer> {
+        let i = self.timers.iter().position(|t| t.timer == timer)?;
+        Some(self.timers.remove(i))
+    }
+
+    pub fn is_running(&self, timer: ObjectId) -> bool {
+        self.timers.iter().any(|t| t.timer == timer)
+    }
+
+    // Takes the earliest run due by `until`, moving the clock to it. Timers
+    // due at the same moment run in the order they were started.
+    pub fn next_due(&mut self, until: f64) -> Option<Timer> {
+        let i = self.timers.iter().enumerate()
+            .filter(|(_, t)| t.next_at <= until)
+            .min_by(|(_, a), (_, b)| a.next_at.partial_cmp(&b.next_at).unwrap())?
+            .0;
+        let timer = &mut self.timers[i];
+        self.now = self.now.max(timer.next_at);
+        timer.count += 1;
+        timer.next_at += timer.interval;
+        let run = timer.clone();
+        if run.count >= run.times {
+            self.timers.remove(i);
+        }
+        Some(run)
+    }
+
+    pub fn advance_to(&mut self, until: f64) {
+        self.now = self.now.max(until);
+    }
+}
+
+impl Trace for Scheduler {
+    fn trace(&self, mark: &mut dyn FnMut(ObjectId)) {
+        for timer in self.timers.iter() {
+            mark(timer.timer);
+            timer.block.trace(mark);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scheduler::Scheduler;
+    use crate::types::Value;
+    use crate::vm::ObjectId;
+
+    fn runs(scheduler: &mut Scheduler, until: f64) -> Vec<(usize, u64)> {
+        std::iter::from_fn(|| scheduler.next_due(until))
+            .map(|run| (run.timer.0, run.count))
+            .collect()
+    }
+
+    #[test]
+    fn timers_run_in_time_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.start(ObjectId(1), Value::Null, 1.0, 3);
+        scheduler.start(ObjectId(2), Value::Null, 0.4, 10);
+
+        assert_eq!(runs(&mut scheduler, 1.0), vec![(2, 1), (2, 2), (1, 1)]);
+        assert_eq!(scheduler.now(), 1.0);
+        scheduler.stop(ObjectId(2));
+        assert_eq!(runs(&mut scheduler, 10.0), vec![(1, 2), (1, 3)]);
+        assert!(!scheduler.is_running(ObjectId(1)));
+    }
+}
@@ -1,18 +1,43 @@
 use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{OnceLock, RwLock};
 
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, Hash, PartialEq, Eq)]
 pub struct SymbolId(usize);
 
 impl SymbolId {
     fn null() -> Self { Self(0) }
     fn num() -> Self { Self(1) }
     fn str() -> Self { Self(2) }
+
+    pub fn name(&self) -> Option<String> {
+        symbol_table().read().unwrap().name(*self).map(|x| x.to_string())
+    }
+}
+
+impl Debug for SymbolId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SymbolId")
+            .field(&self.0)
+            .field(&self.name().unwrap_or_default())
+            .finish()
+    }
+}
+
+impl Display for SymbolId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "#{}", self.0),
+        }
+    }
 }
 
 const USER_SYMBOL_START: usize = 10000usize;
 
 pub struct SymbolTable {
     forward: HashMap<String, SymbolId>,
+    backward: HashMap<SymbolId, String>,
     user_next: usize,
     system_next: usize,
 }
@@ -24,6 +49,7 @@ impl SymbolTable {
 
         Self {
             forward,
+            backward: HashMap::new(),
             user_next: USER_SYMBOL_START,
             system_next: 0usize,
         }
@@ -33,16 +59,20 @@ impl SymbolTable {
         self.forward.get(name).copied()
     }
 
+    pub fn name(&self, sid: SymbolId) -> Option<&str> {
+        self.backward.get(&sid).map(|x| x.as_str())
+    }
+
     pub fn insert_system_symbol(&mut self, name: &str) -> SymbolId {
         let sid = SymbolId(self.system_next);
-        self.forward.insert(name.to_string(), sid);
+        self.insert(name, sid);
         self.system_next += 1;
         sid
     }
 
     pub fn insert_user_symbol(&mut self, name: &str) -> SymbolId {
         let sid = SymbolId(self.user_next);
-        self.forward.insert(name.to_string(), sid);
+        self.insert(name, sid);
         self.user_next += 1;
         sid
     }
@@ -53,4 +83,50 @@ impl SymbolTable {
         }
         self.forward.get(name).copied().unwrap()
     }
+
+    fn insert(&mut self, name: &str, sid: SymbolId) {
+        self.forward.insert(name.to_string(), sid);
+        self.backward.insert(sid, name.to_string());
+    }
+}
+
+// Names are interned once, when the AST is built, and shared by every VM so
+// that parsed programs can be evaluated anywhere without re-resolving. The
+// parser has no VM to hand, and one AST may run in several VMs, so the
+// table cannot belong to a VM. It only grows by the distinct names in the
+// programs parsed, and after the first sighting of a name every lookup,
+// including Display, takes just the read lock.
+fn symbol_table() -> &'static RwLock<SymbolTable> {
+    static SYMBOL_TABLE: OnceLock<RwLock<SymbolTable>> = OnceLock::new();
+    SYMBOL_TABLE.get_or_init(|| RwLock::new(SymbolTable::new()))
+}
+
+pub fn intern(name: &str) -> SymbolId {
+    if let Some(sid) = symbol_table().read().unwrap().get(name) {
+        return sid;
+    }
+    symbol_table().write().unwrap().insert_user_symbol_if_no_exist(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbol::{intern, SymbolTable};
+
+    #[test]
+    fn reverse_lookup() {
+        let mut table = SymbolTable::new();
+        let sid = table.insert_user_symbol_if_no_exist("かめた");
+
+        assert_eq!(table.insert_user_symbol_if_no_exist("かめた"), sid);
+        assert_eq!(table.name(sid), Some("かめた"));
+    }
+
+    #[test]
+    fn intern_is_stable() {
+        let sid = intern("歩く");
+
+        assert_eq!(intern("歩く"), sid);
+        assert_eq!(sid.name(), Some("歩く".to_string()));
+        assert_eq!(sid.to_string(), "歩く");
+    }
 }
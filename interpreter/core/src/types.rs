@@ -1,7 +1,6 @@
 use crate::vm::{ObjectId, VirtualMachine};
 use crate::error::{Error, Result};
 use crate::object::Object;
-use std::sync::Arc;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
@@ -21,6 +20,14 @@ impl Value {
         }
     }
 
+    pub fn as_str(&self) -> Result<&str> {
+        if let Self::Str(s) = self {
+            Ok(s)
+        } else {
+            Err(Error::Runtime)
+        }
+    }
+
     pub fn as_bool(&self) -> Result<bool> {
         if let Self::Bool(b) = self {
             Ok(*b)
@@ -37,7 +44,7 @@ impl Value {
         }
     }
 
-    pub fn as_object(&self, vm: &VirtualMachine) -> Result<Arc<Object>> {
+    pub fn as_object<'a>(&self, vm: &'a VirtualMachine) -> Result<&'a Object> {
         vm.get_object(self.as_object_id()?)
     }
 }
@@ -1,71 +1,217 @@
-use crate::symbol::{SymbolId, SymbolTable};
-use std::collections::HashMap;
+use crate::symbol::{self, SymbolId};
+use std::collections::{HashMap, HashSet};
 use crate::error::{Error, Result};
 use crate::types::Value;
 use crate::object::{Object, ObjectBody};
 use crate::object;
 use crate::ast::{ASTNode, Eval};
-use std::sync::{RwLock, Arc, Mutex, RwLockReadGuard};
+use crate::budget::{Budget, CancelHandle};
+use crate::animation::Animation;
+use crate::scheduler::Scheduler;
+#[cfg(feature = "turtle")]
+use crate::collision::{self, Collisions};
+use crate::gc::Trace;
+use crate::bytecode::{self, Chunk};
+use crate::module;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
 pub struct ObjectId(pub usize);
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Evaluator {
+    TreeWalker,
+    Bytecode,
+}
+
+const DEFAULT_MAX_CALL_DEPTH: usize = 200;
+const DEFAULT_GC_THRESHOLD: usize = 10000;
+
+#[derive(Copy, Clone)]
+struct MethodFrame {
+    method: SymbolId,
+    holder: ObjectId,
+    origin: ObjectId,
+}
+
 pub struct VirtualMachine {
-    object_heap: RwLock<HashMap<ObjectId, Arc<Object>>>,
-    next_object_id: Mutex<usize>,
-    value_assigns_table: RwLock<HashMap<SymbolId, Value>>,
-    symbol_table: RwLock<SymbolTable>,
-    stack: RwLock<Vec<HashMap<SymbolId, Value>>>,
+    object_heap: HashMap<ObjectId, Object>,
+    next_object_id: usize,
+    value_assigns_table: HashMap<SymbolId, Value>,
+    stack: Vec<HashMap<SymbolId, Value>>,
+    method_frames: Vec<MethodFrame>,
+    budget: Budget,
+    max_call_depth: usize,
+    pinned_objects: HashMap<ObjectId, usize>,
+    // values evaluation is holding outside the heap, scopes and stack frames
+    temporary_roots: Vec<Value>,
+    allocations_since_gc: usize,
+    gc_threshold: Option<usize>,
+    evaluator: Evaluator,
+    random_state: u64,
+    animation: Animation,
+    scheduler: Scheduler,
+    #[cfg(feature = "turtle")]
+    collisions: Collisions,
 }
 
 impl VirtualMachine {
     pub fn new() -> Self {
         Self {
-            object_heap: RwLock::new(HashMap::new()),
-            next_object_id: Mutex::new(0),
-            value_assigns_table: RwLock::new(HashMap::new()),
-            symbol_table: RwLock::new(SymbolTable::new()),
-            stack: RwLock::new(vec![]),
+            object_heap: HashMap::new(),
+            next_object_id: 0,
+            value_assigns_table: HashMap::new(),
+            stack: vec![],
+            method_frames: vec![],
+            budget: Budget::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            pinned_objects: HashMap::new(),
+            temporary_roots: vec![],
+            allocations_since_gc: 0,
+            gc_threshold: Some(DEFAULT_GC_THRESHOLD),
+            evaluator: Evaluator::Bytecode,
+            random_state: SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0) | 1,
+            animation: Animation::new(),
+            scheduler: Scheduler::new(),
+            #[cfg(feature = "turtle")]
+            collisions: Collisions::new(),
+        }
+    }
+
+    pub fn eval(&mut self, ast: &ASTNode) -> Result<Value> {
+        match self.evaluator {
+            Evaluator::TreeWalker => ast.eval(self),
+            Evaluator::Bytecode => bytecode::run(&Chunk::compile(ast), self),
+        }
+    }
+
+    pub fn evaluator(&self) -> Evaluator {
+        self.evaluator
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Evaluator) {
+        self.evaluator = evaluator;
+    }
+
+    pub fn set_step_limit(&mut self, max_steps: Option<u64>) {
+        self.budget.set_max_steps(max_steps);
+    }
+
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.budget.set_time_limit(time_limit);
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.budget.cancel_handle()
+    }
+
+    pub fn reset_budget(&mut self) {
+        self.budget.reset();
+    }
+
+    pub fn step(&mut self) -> Result<()> {
+        self.budget.step()
+    }
+
+    // Programs using random values repeat exactly after the same seed.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random_state = seed | 1;
+    }
+
+    // A number in [0, 1) from xorshift64*.
+    pub fn random(&mut self) -> f64 {
+        self.random_state ^= self.random_state >> 12;
+        self.random_state ^= self.random_state << 25;
+        self.random_state ^= self.random_state >> 27;
+        let bits = self.random_state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn animation(&self) -> &Animation {
+        &self.animation
+    }
+
+    pub fn animation_mut(&mut self) -> &mut Animation {
+        &mut self.animation
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    pub fn scheduler_mut(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+
+    #[cfg(feature = "turtle")]
+    pub fn collisions_mut(&mut self) -> &mut Collisions {
+        &mut self.collisions
+    }
+
+    // Moves the virtual clock on by `dt` seconds, running the timers that
+    // fall due on the way and playing animations along. A timer whose block
+    // fails is stopped.
+    pub fn tick(&mut self, dt: f64) -> Result<()> {
+        self.animation.tick(dt);
+        let until = self.scheduler.now() + dt;
+        while let Some(run) = self.scheduler.next_due(until) {
+            // a timer's last run is already off the scheduler
+            let mark = self.push_roots(std::slice::from_ref(&run.block));
+            let result = self.step()
+                .and_then(|_| object::block::exec(&run.block, &vec![Value::Num(run.count as f64)], self));
+            self.pop_roots(mark);
+            if let Err(e) = result {
+                self.scheduler.stop(run.timer);
+                return Err(e);
+            }
+            self.collect_garbage_if_needed();
         }
+        self.scheduler.advance_to(until);
+        #[cfg(feature = "turtle")]
+        collision::detect(self)?;
+        Ok(())
     }
 
-    pub fn eval(&self, ast: &ASTNode) -> Result<Value> {
-        ast.eval(self)
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
     }
 
-    pub fn push_stack(&self, dummy_args: &Vec<String>, real_args: &Vec<Value>) {
+    pub fn call_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn push_stack(&mut self, dummy_args: &Vec<SymbolId>, real_args: &Vec<Value>) -> Result<()> {
+        if self.stack.len() >= self.max_call_depth {
+            return Err(Error::RecursionLimitExceeded);
+        }
         let mut s = HashMap::new();
         for (va, ra) in dummy_args.iter().zip(real_args.iter()) {
-            s.insert(self.to_symbol(va), ra.clone());
+            s.insert(*va, ra.clone());
         }
-        self.stack.write().unwrap().push(s);
+        self.stack.push(s);
+        Ok(())
     }
 
-    pub fn pop_stack(&self) {
-        self.stack.write().unwrap().pop();
+    pub fn pop_stack(&mut self) {
+        self.stack.pop();
     }
 
-    pub fn call_method(&self, this: &Value, method: SymbolId, args: &Vec<Value>) -> Result<Value> {
+    pub fn bind_local(&mut self, symbol: SymbolId, value: Value) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.insert(symbol, value);
+        }
+    }
+
+    pub fn call_method(&mut self, this: &Value, method: SymbolId, args: &Vec<Value>) -> Result<Value> {
         match this {
             Value::ObjectReference(oid) => {
-                let obj = {
-                    let obj_heap = self.object_heap.read().unwrap();
-                    obj_heap.get(&oid).unwrap().clone()
-                };
-                let method_obj = obj.get_member(method);
-                match method_obj {
-                    Ok(x) => {
-                        object::block::exec(&x,
-                                            args, self)
-                    }
-                    Err(Error::MemberNotFound) => {
-                        let method = obj.get_method(method)?;
-                        method(this, args, self)
-                    }
-                    Err(e) => {
-                        Err(e)
-                    }
-                }
+                let mark = self.push_roots(std::slice::from_ref(this));
+                self.push_roots(args);
+                let result = self.dispatch(this, *oid, method, args);
+                self.pop_roots(mark);
+                result
             }
             _ => {
                 Err(Error::Runtime)
@@ -73,32 +219,175 @@ impl VirtualMachine {
         }
     }
 
-    pub fn assign(&self, target: SymbolId, value: &Value) -> Result<()> {
-        self.value_assigns_table.write().unwrap().insert(target, value.clone());
+    // Calls the next implementation of the running block method after the
+    // object it was found on, following the receiver's lookup order so
+    // chains of overrides each go one step further.
+    pub fn call_super(&mut self, this: &Value, args: &Vec<Value>) -> Result<Value> {
+        let frame = self.method_frames.last().copied().ok_or(Error::Runtime)?;
+        let (member, native) = {
+            let chain = self.get_object(frame.origin)?.ancestors(self);
+            let rest: Vec<&Object> = chain.into_iter()
+                .skip_while(|obj| obj.id() != frame.holder)
+                .skip(1)
+                .collect();
+            let member = rest.iter()
+                .find_map(|obj| obj.own_member(frame.method).map(|x| (obj.id(), x)));
+            let native = rest.iter().find_map(|obj| obj.own_method(frame.method));
+            (member, native)
+        };
+        match (member, native) {
+            (Some((holder, x)), _) => {
+                self.run_method(this, MethodFrame { holder, ..frame }, &x, args)
+            }
+            (None, Some(method)) => method(this, args, self),
+            (None, None) => Err(Error::MethodNotFound),
+        }
+    }
+
+    fn dispatch(&mut self, this: &Value, origin: ObjectId, method: SymbolId, args: &Vec<Value>) -> Result<Value> {
+        let obj = self.get_object(origin)?;
+        match obj.lookup_member(method, self) {
+            Ok((holder, x)) => {
+                self.run_method(this, MethodFrame { method, holder, origin }, &x, args)
+            }
+            Err(Error::MemberNotFound) => {
+                let method = obj.get_method(method, self)?;
+                method(this, args, self)
+            }
+            Err(e) => {
+                Err(e)
+            }
+        }
+    }
+
+    fn run_method(&mut self, this: &Value, frame: MethodFrame, block: &Value, args: &Vec<Value>) -> Result<Value> {
+        self.method_frames.push(frame);
+        let result = object::block::exec_method(block, this, args, self);
+        self.method_frames.pop();
+        result
+    }
+
+    // Adds `mixin` as a further delegate of `object`, consulted after the ones
+    // it already has. Mixing in a descendant would make lookup loop forever.
+    pub fn add_mixin(&mut self, object: ObjectId, mixin: ObjectId) -> Result<()> {
+        if object == mixin || self.get_object(mixin)?.is_subclass(object, self) {
+            return Err(Error::ArgumentError);
+        }
+        self.get_object_mut(object)?.add_parent(mixin);
+        Ok(())
+    }
+
+    // Stores a member the program assigned, as opposed to natives filling
+    // in their own state.
+    pub fn assign_member(&mut self, object: &Value, member: SymbolId, value: Value) -> Result<()> {
+        self.get_object_mut_from_value(object)?.set_member(member, value);
+        #[cfg(feature = "turtle")]
+        self.collisions.member_assigned(member);
         Ok(())
     }
 
-    pub fn allocate(&self, object_body: ObjectBody) -> Result<ObjectId> {
-        let id = ObjectId(*self.next_object_id.lock().unwrap());
-        *self.next_object_id.lock().unwrap() = id.0 + 1;
+    pub fn assign(&mut self, target: SymbolId, value: &Value) -> Result<()> {
+        self.value_assigns_table.insert(target, value.clone());
+        Ok(())
+    }
 
-        let object = Object::new(id, object_body);
-        self.object_heap.write().unwrap()
-            .insert(id, Arc::new(object));
+    pub fn allocate(&mut self, object_body: ObjectBody) -> Result<ObjectId> {
+        let id = ObjectId(self.next_object_id);
+        self.next_object_id = id.0 + 1;
+
+        self.object_heap.insert(id, Object::new(id, object_body));
+        self.allocations_since_gc += 1;
         Ok(id)
     }
 
-    pub fn get_object(&self, object_id: ObjectId) -> Result<Arc<Object>> {
-        self.object_heap.read().unwrap()
-            .get(&object_id)
-            .ok_or(Error::ObjectNotFound).map(|x| x.clone())
+    pub fn pin_object(&mut self, object_id: ObjectId) {
+        *self.pinned_objects.entry(object_id).or_insert(0) += 1;
+    }
+
+    pub fn unpin_object(&mut self, object_id: ObjectId) {
+        if let Some(count) = self.pinned_objects.get_mut(&object_id) {
+            *count -= 1;
+            if *count == 0 {
+                self.pinned_objects.remove(&object_id);
+            }
+        }
+    }
+
+    // Keeps `values` alive through collections until `pop_roots` is given the
+    // returned mark.
+    pub fn push_roots(&mut self, values: &[Value]) -> usize {
+        let mark = self.temporary_roots.len();
+        self.temporary_roots.extend(values.iter().filter(|v| matches!(v, Value::ObjectReference(_))).cloned());
+        mark
+    }
+
+    pub fn pop_roots(&mut self, mark: usize) {
+        self.temporary_roots.truncate(mark);
+    }
+
+    pub fn set_gc_threshold(&mut self, gc_threshold: Option<usize>) {
+        self.gc_threshold = gc_threshold;
+    }
+
+    // Values held only in Rust locals are not roots unless passed to
+    // `push_roots`. Receivers and arguments of calls in progress always are.
+    pub fn collect_garbage(&mut self) -> usize {
+        let mut pending: Vec<ObjectId> = vec![];
+        {
+            let mut mark = |id: ObjectId| pending.push(id);
+            for v in self.value_assigns_table.values() {
+                v.trace(&mut mark);
+            }
+            for frame in self.stack.iter() {
+                for v in frame.values() {
+                    v.trace(&mut mark);
+                }
+            }
+            for id in self.pinned_objects.keys() {
+                mark(*id);
+            }
+            for v in self.temporary_roots.iter() {
+                v.trace(&mut mark);
+            }
+            self.scheduler.trace(&mut mark);
+        }
+
+        let mut marked = HashSet::new();
+        while let Some(id) = pending.pop() {
+            if !marked.insert(id) {
+                continue;
+            }
+            if let Some(object) = self.object_heap.get(&id) {
+                object.trace(&mut |child| pending.push(child));
+            }
+        }
+
+        self.allocations_since_gc = 0;
+        let before = self.object_heap.len();
+        self.object_heap.retain(|id, _| marked.contains(id));
+        before - self.object_heap.len()
+    }
+
+    pub fn collect_garbage_if_needed(&mut self) -> usize {
+        match self.gc_threshold {
+            Some(threshold) if self.allocations_since_gc >= threshold => self.collect_garbage(),
+            _ => 0,
+        }
+    }
+
+    pub fn get_object(&self, object_id: ObjectId) -> Result<&Object> {
+        self.object_heap.get(&object_id).ok_or(Error::ObjectNotFound)
+    }
+
+    pub fn get_object_mut(&mut self, object_id: ObjectId) -> Result<&mut Object> {
+        self.object_heap.get_mut(&object_id).ok_or(Error::ObjectNotFound)
     }
 
     pub fn get_block_object_value(&self) -> Result<Value> {
         Ok(Value::ObjectReference(self.get_object_id_in_assigns(self.to_symbol("ブロック"))?))
     }
 
-    pub fn get_object_from_value(&self, value :&Value) -> Result<Arc<Object>> {
+    pub fn get_object_from_value(&self, value :&Value) -> Result<&Object> {
         match value {
             Value::ObjectReference(obj_id) => {
                 self.get_object(*obj_id)
@@ -109,14 +398,25 @@ impl VirtualMachine {
         }
     }
 
-    pub fn get_object_in_assigns_from_symbol(&self, symbol: &str) -> Result<Arc<Object>> {
+    pub fn get_object_mut_from_value(&mut self, value :&Value) -> Result<&mut Object> {
+        match value {
+            Value::ObjectReference(obj_id) => {
+                self.get_object_mut(*obj_id)
+            }
+            _ => {
+                Err(Error::Runtime)
+            }
+        }
+    }
+
+    pub fn get_object_in_assigns_from_symbol(&self, symbol: &str) -> Result<&Object> {
         self.get_object(
             self.get_object_id_in_assigns(
                 self.to_symbol(symbol))?)
     }
 
-    pub fn get_object_heap(&self) -> RwLockReadGuard<HashMap<ObjectId, Arc::<Object>>> {
-        self.object_heap.read().unwrap()
+    pub fn get_object_heap(&self) -> &HashMap<ObjectId, Object> {
+        &self.object_heap
     }
 
     pub fn get_object_id_in_assigns(&self, symbol_id: SymbolId) -> Result<ObjectId> {
@@ -124,15 +424,13 @@ impl VirtualMachine {
     }
 
     fn get_value_in_assigns(&self, symbol_id: SymbolId) -> Result<Value> {
-        let assigns_table = self.value_assigns_table
-            .read().unwrap();
-        assigns_table.get(&symbol_id)
+        self.value_assigns_table.get(&symbol_id)
             .cloned()
             .ok_or(Error::ObjectNotFound)
     }
 
     pub fn get_value_in_scope(&self, symbol_id: SymbolId) -> Result<Value> {
-        self.stack.read().unwrap().iter().rev().find_map(|x| x.get(&symbol_id).cloned())
+        self.stack.iter().rev().find_map(|x| x.get(&symbol_id).cloned())
             .ok_or(Error::ObjectNotFound)
             .or_else(|_| {
                 self.get_value_in_assigns(symbol_id)
@@ -144,91 +442,21 @@ impl VirtualMachine {
         self.get_value_in_scope(sym_id)
     }
 
-    pub fn object_heap_borrow(&self) -> RwLockReadGuard<HashMap<ObjectId, Arc<Object>>>{
-        self.object_heap.read().unwrap()
+    pub fn object_heap_borrow(&self) -> &HashMap<ObjectId, Object> {
+        &self.object_heap
     }
 
     pub fn to_symbol(&self, symbol_str: &str) -> SymbolId {
-        self.symbol_table.write().unwrap().insert_user_symbol_if_no_exist(symbol_str)
+        symbol::intern(symbol_str)
     }
 
-    pub fn initialize(&mut self) {
-        let root_obj_id = {
-            let mut root = ObjectBody::empty();
-            root.add_method(self.to_symbol("作る"), object::root::create);
-            let root_obj_id = self.allocate(root).unwrap();
-            self.assign(self.to_symbol("ルート"), &Value::ObjectReference(root_obj_id)).unwrap();
-            root_obj_id
-        };
-
-        let _block_obj_id = {
-            let block_value = &object::root::create(
-                &Value::ObjectReference(root_obj_id), &vec![], self
-            ).unwrap().clone();
-            let block = self.get_object_from_value(
-                &block_value
-            ).unwrap();
-
-            block.add_method(
-                self.to_symbol("実行"),
-                object::block::exec
-            );
-
-            block.add_method(
-                self.to_symbol("繰り返す"),
-                object::block::repeat,
-            );
-
-            block.add_method(
-                self.to_symbol("ならば"),
-                object::block::if_,
-            );
-
-            let block_symbol = self.to_symbol("ブロック");
-            self.assign(block_symbol, &block_value).unwrap();
-
-            block_value.as_object_id().unwrap()
-        };
-
-        let _turtle_obj_id = {
-            let turtle_value = &object::root::create(
-                &Value::ObjectReference(root_obj_id), &vec![], self
-            ).unwrap().clone();
-            let turtle = self.get_object_from_value(
-                &turtle_value
-            ).unwrap();
-            turtle.add_method(self.to_symbol("歩く"),
-                              object::turtle::walk);
-            turtle.add_method(self.to_symbol("右回り"),
-                              object::turtle::turn_right);
-            turtle.add_method(self.to_symbol("左回り"),
-                              object::turtle::turn_left);
-            turtle.add_method(self.to_symbol("作る"),
-                                             object::turtle::create);
-            turtle.set_member(self.to_symbol("x"), Value::Num(0.0));
-            turtle.set_member(self.to_symbol("y"), Value::Num(0.0));
-            turtle.set_member(self.to_symbol("direction"), Value::Num(0.0));
-            turtle.set_member(self.to_symbol("visible"),
-                              Value::Bool(false));
-            let turtle_symbol = self.to_symbol("タートル");
-            self.assign(turtle_symbol, &turtle_value).unwrap();
-            turtle_value.as_object_id().unwrap()
-        };
-
-        let _line_obj_id = {
-            let line_value = &object::root::create(
-                &Value::ObjectReference(root_obj_id), &vec![], self
-            ).unwrap().clone();
-            let line_symbol = self.to_symbol("線");
-            self.assign(line_symbol, &line_value).unwrap();
-            line_value.as_object_id().unwrap()
-        };
-
-        let _condition_obj_id = object::condition::create_super_object(
-            root_obj_id, self
-        ).unwrap();
+    pub fn symbol_name(&self, symbol_id: SymbolId) -> Option<String> {
+        symbol_id.name()
+    }
 
-        let _button_obj_id =
-            object::button::create_super_object(root_obj_id, self).unwrap();
+    pub fn initialize(&mut self) {
+        for module in module::standard_modules() {
+            module.install(self).unwrap();
+        }
     }
 }
@@ -9,7 +9,7 @@ use std::rc::Rc;
 use core::ast::ASTNode;
 use nom::sequence::{terminated, separated_pair, delimited};
 use unicode_num::ParseUnicodeExt;
-use nom::combinator::{iterator, complete, opt, all_consuming};
+use nom::combinator::{iterator, complete, opt, all_consuming, recognize};
 use nom::multi::many1;
 use nom::bytes::complete::{take_until, take_till};
 use nom::Err::Error;
@@ -26,6 +26,11 @@ pub fn parse_program_code(input: &str) -> IResult<&str, Vec<ASTNode>> {
     )))))(input)
 }
 
+// A single expression, with or without the closing 。.
+pub fn parse_expression(input: &str) -> IResult<&str, ASTNode> {
+    all_consuming(terminated(form, opt(end_of_term)))(input)
+}
+
 #[derive(Debug, PartialOrd, PartialEq, Eq, Copy, Clone)]
 enum SpecialToken {
     Plus,
@@ -50,6 +55,7 @@ enum SpecialToken {
     EndOfTerm,
     Comma,
     Colon,
+    DoubleQuote,
 }
 
 fn lt(input: &str) -> IResult<&str, SpecialToken> {
@@ -134,6 +140,12 @@ fn colon(input: &str) -> IResult<&str, SpecialToken> {
     ))(input)
 }
 
+fn double_quote(input: &str) -> IResult<&str, SpecialToken> {
+    value(SpecialToken::DoubleQuote, alt(
+        (tag("\""), tag("＂"))
+    ))(input)
+}
+
 fn plus_minus(input: &str) -> IResult<&str, SpecialToken> {
     alt((
         value(SpecialToken::Plus, alt((tag("+"), tag("＋")))),
@@ -162,6 +174,7 @@ fn specials(input: &str) -> IResult<&str, SpecialToken> {
         comma,
         pipe,
         colon,
+        double_quote,
         lt,
         lte,
         gt,
@@ -208,6 +221,7 @@ fn form(input: &str) -> IResult<&str, ASTNode> {
             block,
             decl,
             num_static_value,
+            str_static_value,
         )
     )(input)
 }
@@ -260,7 +274,8 @@ fn decl(input: &str) -> IResult<&str, ASTNode> {
 }
 
 fn num(input: &str) -> IResult<&str, core::types::Value> {
-    map(nom_unicode::complete::digit1, |x: &str| {
+    let digit1 = nom_unicode::complete::digit1;
+    map(recognize(tuple((digit1, opt(tuple((alt((tag("."), tag("．"))), digit1)))))), |x: &str| {
         core::types::Value::Num(x.parse_unicode().unwrap())
     })(input)
 }
@@ -269,6 +284,12 @@ fn num_static_value(input: &str) -> IResult<&str, ASTNode> {
     map(num, |x| ASTNode::new_static_value(&x))(input)
 }
 
+fn str_static_value(input: &str) -> IResult<&str, ASTNode> {
+    map(delimited(double_quote, take_till(|c| c == '"' || c == '＂'), double_quote), |x: &str| {
+        ASTNode::new_static_value(&core::types::Value::Str(x.to_string()))
+    })(input)
+}
+
 fn whitespace_delimited<I, O1, F, E: ParseError<I>>(mut sep: F) -> impl FnMut(I) -> IResult<I, O1, E>
 where
     I: InputTakeAtPosition,
@@ -397,6 +418,7 @@ fn single_value(input: &str) -> IResult<&str, ASTNode> {
     alt(
         (
             num_static_value,
+            str_static_value,
             block,
             decl,
             delimited(open_parentheses, form, close_parentheses)
@@ -409,6 +431,7 @@ fn single_value_without_decl(input: &str) -> IResult<&str, ASTNode> {
     alt(
         (
             num_static_value,
+            str_static_value,
             block,
             delimited(open_parentheses, form, close_parentheses)
         )
@@ -482,7 +505,7 @@ fn block(input: &str) -> IResult<&str, ASTNode> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{specials, SpecialToken, symbol, decl, form, code_block, method_call, parse_program_code, block, dummy_args_list, symbol_or_member, assign, or_term, plus_minus};
+    use crate::{specials, SpecialToken, symbol, decl, form, code_block, method_call, parse_program_code, parse_expression, block, dummy_args_list, symbol_or_member, assign, or_term, plus_minus};
     use nom::{
         IResult,
         Err,
@@ -555,6 +578,28 @@ mod tests {
         assert_eq!(method_call(input), expected);
     }
 
+    #[rstest(input, expected,
+        case("かめた！\"car.png\"　変身する", Ok((
+            "",
+            ASTNode::new_method_call(
+                "変身する",
+                &ASTNode::new_decl(&None, "かめた"),
+                &vec![ASTNode::new_static_value(&Value::Str("car.png".to_string()))]
+            )
+        ))),
+        case("かめた！＂＂　変身する", Ok((
+            "",
+            ASTNode::new_method_call(
+                "変身する",
+                &ASTNode::new_decl(&None, "かめた"),
+                &vec![ASTNode::new_static_value(&Value::Str("".to_string()))]
+            )
+        ))),
+    )]
+    fn parse_string_argument(input: &str, expected: IResult<&str, ASTNode>) {
+        assert_eq!(method_call(input), expected);
+    }
+
     #[rstest(input, expected,
         case("｜歩幅｜", Ok(("", vec!["歩幅".to_string()]))),
         case("|歩幅|", Ok(("", vec!["歩幅".to_string()]))),
@@ -685,6 +730,8 @@ mod tests {
     }
 
     #[rstest(input, expected,
+        case("０．５ + 1", Ok(("",
+            ASTNode::new_add(&ASTNode::new_static_value(&Value::Num(0.5)), &ASTNode::new_static_value(&Value::Num(1.0)))))),
         case("1 + 1", Ok(("",
             ASTNode::new_add(&ASTNode::new_static_value(&Value::Num(1.0)), &ASTNode::new_static_value(&Value::Num(1.0)))))),
         case("1-1", Ok(("",
@@ -750,6 +797,17 @@ mod tests {
     )));
     }
 
+    #[test]
+    fn parse_expression_test() {
+        let expected = ASTNode::new_add(
+            &ASTNode::new_decl(&None, "てすと"),
+            &ASTNode::new_static_value(&Value::Num(1.0)),
+        );
+        assert_eq!(parse_expression("てすと＋１"), Ok(("", expected.clone())));
+        assert_eq!(parse_expression("てすと＋１。"), Ok(("", expected)));
+        assert!(parse_expression("てすと＋１。てすと。").is_err());
+    }
+
     #[test]
     fn awesome_check() {
         let target = "タートル！作る";
@@ -1,10 +1,18 @@
-use core::vm::{VirtualMachine, ObjectId};
+use core::vm::{VirtualMachine, ObjectId, Evaluator};
 use core::ast::ASTNode;
 use core::object::Object;
+use core::budget::CancelHandle;
+use core::prototype::PrototypeBuilder;
+use core::error::{Error, Result};
+use core::types::Value;
 use std::collections::HashMap;
 use std::rc::Rc;
 use core::symbol::SymbolId;
-use std::sync::Arc;
+use std::time::Duration;
+
+mod shared;
+
+pub use shared::SharedInterpreter;
 
 pub struct Interpreter {
     vm: VirtualMachine,
@@ -12,15 +20,80 @@ pub struct Interpreter {
 
 impl Interpreter {
     pub fn exec(&mut self, program: &str) {
-        let (_, asts)= parser::parse_program_code(program).unwrap();
+        self.try_exec(program).unwrap();
+    }
+
+    pub fn try_exec(&mut self, program: &str) -> Result<()> {
+        let (_, asts) = parser::parse_program_code(program)
+            .map_err(|_| Error::SyntaxError)?;
+        self.vm.reset_budget();
         for ast in asts {
-            self.vm.eval(&ast).unwrap();
+            self.vm.eval(&ast)?;
+            self.vm.collect_garbage_if_needed();
         }
+        Ok(())
+    }
+
+    // Returned objects are not roots; assign them to a variable to keep them
+    // alive across later `exec` calls.
+    pub fn eval(&mut self, expression: &str) -> Result<Value> {
+        let (_, ast) = parser::parse_expression(expression.trim())
+            .map_err(|_| Error::SyntaxError)?;
+        self.vm.reset_budget();
+        self.vm.eval(&ast)
+    }
+
+    pub fn call_method(&mut self, receiver: &Value, name: &str, args: &[Value]) -> Result<Value> {
+        self.vm.reset_budget();
+        self.vm.call_method(receiver, self.vm.to_symbol(name), &args.to_vec())
+    }
+
+    pub fn collect_garbage(&mut self) -> usize {
+        self.vm.collect_garbage()
     }
 
-    pub fn get_objects(&self) -> Vec<Arc<Object>> {
-        self.vm.get_object_heap().iter()
-            .map(|x| x.1.clone()).collect()
+    pub fn set_gc_threshold(&mut self, gc_threshold: Option<usize>) {
+        self.vm.set_gc_threshold(gc_threshold);
+    }
+
+    pub fn set_step_limit(&mut self, max_steps: Option<u64>) {
+        self.vm.set_step_limit(max_steps);
+    }
+
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.vm.set_time_limit(time_limit);
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.vm.cancel_handle()
+    }
+
+    // Off by default, so turtles are drawn where the program leaves them.
+    pub fn set_animated(&mut self, animated: bool) {
+        self.vm.animation_mut().set_enabled(animated);
+    }
+
+    // Advances timers and animated turtles by `dt` seconds; called by the
+    // host each frame.
+    pub fn tick(&mut self, dt: f64) -> Result<()> {
+        self.vm.reset_budget();
+        self.vm.tick(dt)
+    }
+
+    pub fn install(&mut self, prototype: PrototypeBuilder) -> Result<ObjectId> {
+        prototype.install(&mut self.vm)
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Evaluator) {
+        self.vm.set_evaluator(evaluator);
+    }
+
+    pub fn get_objects(&self) -> Vec<&Object> {
+        self.vm.get_object_heap().values().collect()
+    }
+
+    pub fn vm(&self) -> &VirtualMachine {
+        &self.vm
     }
 
     pub fn get_symbol(&self, s: &str) -> SymbolId {
@@ -42,12 +115,20 @@ impl Interpreter {
             vm
         }
     }
+
+    pub fn from_vm(vm: VirtualMachine) -> Self {
+        Self {
+            vm
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::vm::VirtualMachine;
+    use core::vm::{VirtualMachine, Evaluator};
     use crate::Interpreter;
+    use core::error::Error;
+    use core::types::Value;
     use utilities::test_helper::{nearly_equal, nearly_equal_with_eps};
 
     const eps: f64 = 1e-5;
@@ -72,6 +153,7 @@ mod tests {
     #[test]
     fn test_kameta_create_and_turnleft90_and_walk() {
         let mut interpreter = Interpreter::new();
+        let before = interpreter.get_objects().len();
 
         interpreter.exec(r#"かめた＝タートル！作る。
 かめた！ ９０　左回り １００　歩く。
@@ -87,7 +169,8 @@ mod tests {
         assert!(nearly_equal_with_eps(
             90.0, kameta.get_member_str("direction", &vm).unwrap().as_num().unwrap(), eps));
         assert!(kameta.get_member_str("visible", &vm).unwrap().as_bool().unwrap());
-        assert_eq!(vm.get_object_heap().len(), 9);
+        // かめた and the line it drew
+        assert_eq!(vm.get_object_heap().len(), before + 2);
     }
 
     #[test]
@@ -143,4 +226,690 @@ mod tests {
         interpreter.exec("「てすと＝＝０。」！ならば　「てすと２＝２。」　実行　そうでないなら　「てすと３＝３。」　実行。");
         assert_eq!(interpreter.vm.get_value_in_scope_from_symbol("てすと３").unwrap().as_num().unwrap(), 3.0);
     }
+
+    #[test]
+    fn test_step_limit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_step_limit(Some(10000));
+
+        let result = interpreter.try_exec("「てすと＝１。」！1000000000　繰り返す。");
+        assert!(matches!(result, Err(Error::StepLimitExceeded)));
+
+        interpreter.exec("てすと＝２。");
+        assert_eq!(interpreter.vm.get_value_in_scope_from_symbol("てすと").unwrap().as_num().unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut interpreter = Interpreter::new();
+        interpreter.cancel_handle().cancel();
+
+        // a fresh run clears a cancellation requested while idle
+        interpreter.exec("てすと＝１。");
+        assert_eq!(interpreter.vm.get_value_in_scope_from_symbol("てすと").unwrap().as_num().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let mut interpreter = Interpreter::new();
+
+        assert!(matches!(interpreter.try_exec("かめた＝"), Err(Error::SyntaxError)));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec("かめた＝タートル！作る。");
+        interpreter.exec("かめた：再帰＝「かめた！再帰。」。");
+        let result = interpreter.try_exec("かめた！再帰。");
+        assert!(matches!(result, Err(Error::RecursionLimitExceeded)));
+
+        interpreter.exec("かめた！１００　歩く。");
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_gc_threshold(Some(100));
+
+        interpreter.exec("てすと＝１。");
+        let before_loop = interpreter.get_objects().len();
+        interpreter.exec("「「てすと＝＝１。」！ならば　「てすと２＝２。」　実行。」！１０００　繰り返す。");
+        assert!(interpreter.get_objects().len() < before_loop + 100);
+        interpreter.collect_garbage();
+        assert_eq!(interpreter.get_objects().len(), before_loop);
+
+        interpreter.set_gc_threshold(None);
+        interpreter.exec("「「てすと＝＝１。」！ならば　「てすと２＝２。」　実行。」！１０　繰り返す。");
+        assert!(interpreter.get_objects().len() > before_loop);
+        assert!(interpreter.collect_garbage() > 0);
+        assert_eq!(interpreter.get_objects().len(), before_loop);
+    }
+
+    #[test]
+    fn test_garbage_collection_inside_loops() {
+        for evaluator in vec![Evaluator::TreeWalker, Evaluator::Bytecode] {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_evaluator(evaluator);
+            interpreter.set_gc_threshold(Some(10));
+
+            interpreter.exec("ごみ＝「ルート！作る。」。 時計＝タイマー！作る　１　間隔　１００　回数。 時計！（ごみ） 実行。");
+            let before = interpreter.get_objects().len();
+            let block = interpreter.eval("ごみ").unwrap();
+            interpreter.call_method(&block, "繰り返す", &[Value::Num(100.0)]).unwrap();
+            assert!(interpreter.get_objects().len() < before + 10);
+            interpreter.tick(100.0).unwrap();
+            assert!(interpreter.get_objects().len() < before + 10);
+
+            // the receiver and arguments waiting for a call survive collections
+            // made while evaluating the rest of the call
+            interpreter.exec("はこ＝（ルート！作る）！（「ルート！作る。」！５０ 繰り返す） 混ぜる。");
+            interpreter.exec("（「はこ。」！５０ 繰り返す）：中身＝（ルート！作る）。");
+            assert_eq!(array_elements(&mut interpreter, "はこ：中身！変数一覧"), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_evaluators_agree() {
+        let program = r#"かめた＝タートル！作る。
+かめた：四角＝「｜長さ｜ 「かめた！（長さ） 歩く。 かめた！９０ 右回り。」！４ 繰り返す。」。
+かめた！１００　四角。
+かめた！３０　左回り。
+「てすと＝てすと＋１。」！１０　繰り返す。
+"#;
+        let mut results = vec![];
+        for evaluator in vec![Evaluator::TreeWalker, Evaluator::Bytecode] {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_evaluator(evaluator);
+            interpreter.exec("てすと＝０。");
+            interpreter.exec(program);
+
+            let vm = interpreter.vm;
+            let kameta = vm.get_object_in_assigns_from_symbol("かめた").unwrap();
+            results.push((
+                kameta.get_member_str("x", &vm).unwrap(),
+                kameta.get_member_str("y", &vm).unwrap(),
+                kameta.get_member_str("direction", &vm).unwrap(),
+                vm.get_value_in_scope_from_symbol("てすと").unwrap(),
+                vm.get_object_heap().len(),
+            ));
+        }
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[1].3.as_num().unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_prototype_delegation() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+タートル：二歩＝「｜ｎ｜ かめた！（ｎ） 歩く （ｎ） 歩く。」。
+タートル：歩幅＝５０。
+かめた！（かめた：歩幅）　二歩。
+"#);
+
+        let vm = interpreter.vm;
+        let kameta = vm.get_object_in_assigns_from_symbol("かめた").unwrap();
+        assert!(nearly_equal(
+            100.0, kameta.get_member_str("x", &vm).unwrap().as_num().unwrap()));
+        let turtle = vm.get_object_in_assigns_from_symbol("タートル").unwrap();
+        assert!(nearly_equal(0.0, turtle.get_member_str("x", &vm).unwrap().as_num().unwrap()));
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた！１００　歩く。
+かめこ＝かめた！複製。
+かめた！１００　歩く。
+"#);
+
+        let vm = interpreter.vm;
+        let kameta = vm.get_object_in_assigns_from_symbol("かめた").unwrap();
+        let kameko = vm.get_object_in_assigns_from_symbol("かめこ").unwrap();
+        assert!(nearly_equal(
+            200.0, kameta.get_member_str("x", &vm).unwrap().as_num().unwrap()));
+        assert!(nearly_equal(
+            100.0, kameko.get_member_str("x", &vm).unwrap().as_num().unwrap()));
+        assert!(kameko.get_member_str("visible", &vm).unwrap().as_bool().unwrap());
+        assert_eq!(kameko.parent(), kameta.parent());
+    }
+
+    #[test]
+    fn test_install_prototype() {
+        use core::prototype::PrototypeBuilder;
+
+        let mut interpreter = Interpreter::new();
+        interpreter.install(PrototypeBuilder::new("ロボット")
+            .parent("タートル")
+            .member("電池", Value::Num(100.0))
+            .method("充電", |this, _args, vm| {
+                vm.get_object_mut_from_value(this)?.set_member_str("電池", Value::Num(100.0));
+                Ok(this.clone())
+            })).unwrap();
+
+        interpreter.exec(r#"ロボ＝ロボット！作る。
+ロボ：電池＝１０。
+ロボ！１００　歩く　充電。
+"#);
+
+        let vm = interpreter.vm;
+        let robo = vm.get_object_in_assigns_from_symbol("ロボ").unwrap();
+        assert!(nearly_equal(100.0, robo.get_member_str("x", &vm).unwrap().as_num().unwrap()));
+        assert!(nearly_equal(100.0, robo.get_member_str("電池", &vm).unwrap().as_num().unwrap()));
+    }
+
+    #[test]
+    fn test_math() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"てすと＝数学！１６ 平方根。
+てすと２＝数学！３ １ ２ 最大。
+"#);
+
+        let vm = interpreter.vm;
+        assert_eq!(vm.get_value_in_scope_from_symbol("てすと").unwrap().as_num().unwrap(), 4.0);
+        assert_eq!(vm.get_value_in_scope_from_symbol("てすと２").unwrap().as_num().unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_array() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"はこ＝配列！１ ２ 作る。
+はこ！３ 書く。
+てすと＝はこ！要素数。
+てすと２＝はこ！２ 読む。
+合計＝０。
+はこ！「｜ｎ｜ 合計＝合計＋ｎ。」 それぞれ実行。
+"#);
+
+        let vm = interpreter.vm;
+        assert_eq!(vm.get_value_in_scope_from_symbol("てすと").unwrap().as_num().unwrap(), 3.0);
+        assert_eq!(vm.get_value_in_scope_from_symbol("てすと２").unwrap().as_num().unwrap(), 2.0);
+        assert_eq!(vm.get_value_in_scope_from_symbol("合計").unwrap().as_num().unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_symbols_shared_between_vms() {
+        let (_, asts) = parser::parse_program_code("はこ＝２。 はこ＝はこ＊はこ。").unwrap();
+        let mut first = Interpreter::new();
+        let mut second = Interpreter::new();
+        for ast in asts.iter() {
+            first.vm.eval(ast).unwrap();
+            second.vm.eval(ast).unwrap();
+        }
+        first.exec("はこ＝はこ＋１。");
+        assert_eq!(first.eval("はこ").unwrap(), Value::Num(5.0));
+        assert_eq!(second.eval("はこ").unwrap(), Value::Num(4.0));
+
+        let threads: Vec<_> = ["かめた", "かめきち"].iter().map(|name| {
+            std::thread::spawn(move || {
+                let mut interpreter = Interpreter::new();
+                interpreter.exec(&format!("{}＝タートル！作る　１０　歩く。", name));
+                interpreter.eval(&format!("{}：x", name)).unwrap()
+            })
+        }).collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), Value::Num(10.0));
+        }
+        assert_eq!(core::symbol::intern("かめきち").to_string(), "かめきち");
+    }
+
+    #[test]
+    fn test_headless_vm() {
+        use core::module::VirtualMachineBuilder;
+
+        let mut interpreter = Interpreter::from_vm(VirtualMachineBuilder::new().build().unwrap());
+
+        interpreter.exec("てすと＝０。「てすと＝てすと＋１。」！３ 繰り返す。");
+        assert!(matches!(interpreter.try_exec("かめた＝タートル！作る。"), Err(Error::ObjectNotFound)));
+        assert_eq!(interpreter.vm.get_value_in_scope_from_symbol("てすと").unwrap().as_num().unwrap(), 3.0);
+
+        interpreter.exec("はこ＝ルート！作る。 はこ：中身＝１。");
+        assert_eq!(array_elements(&mut interpreter, "はこ！変数一覧"), vec![Value::Str("中身".to_string())]);
+        assert!(!array_elements(&mut interpreter, "はこ！メソッド一覧").is_empty());
+    }
+
+    #[test]
+    fn test_turtle_only_vm() {
+        use core::module::{ColorModule, TurtleModule, VirtualMachineBuilder};
+
+        let vm = VirtualMachineBuilder::new().module(ColorModule).module(TurtleModule).build().unwrap();
+        let mut interpreter = Interpreter::from_vm(vm);
+
+        interpreter.exec("かめた＝タートル！作る　３０　４０　移動する。");
+        assert_eq!(array_elements(&mut interpreter, "かめた！位置"), vec![Value::Num(30.0), Value::Num(40.0)]);
+    }
+
+    #[test]
+    fn test_call_method() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた：四角＝「｜長さ｜ 「かめた！（長さ） 歩く。 かめた！９０ 右回り。」！４ 繰り返す。 長さ＊４。」。
+押した＝０。
+ぼたん＝ボタン！作る。
+ぼたん：動作＝「押した＝押した＋１。」。
+"#);
+
+        let kameta = interpreter.eval("かめた").unwrap();
+        let result = interpreter.call_method(&kameta, "四角", &[Value::Num(50.0)]).unwrap();
+        assert_eq!(result, Value::Num(200.0));
+        let lines: Vec<_> = interpreter.vm.get_object_heap().values()
+            .filter(|obj| obj.get_member_str("x1", &interpreter.vm).is_ok()).collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(core::object::line::points(lines[0], &interpreter.vm).unwrap().len(), 5);
+
+        let button = interpreter.eval("ぼたん").unwrap();
+        interpreter.call_method(&button, "クリック", &[]).unwrap();
+        interpreter.call_method(&button, "クリック", &[]).unwrap();
+        assert_eq!(interpreter.eval("押した。").unwrap(), Value::Num(2.0));
+
+        assert!(matches!(interpreter.call_method(&kameta, "飛ぶ", &[]), Err(Error::MethodNotFound)));
+    }
+
+    #[test]
+    fn test_eval_expression() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec("てすと＝２。");
+        assert_eq!(interpreter.eval("てすと＊３＋１").unwrap(), Value::Num(7.0));
+        assert_eq!(interpreter.eval("数学！１６ 平方根").unwrap(), Value::Num(4.0));
+        assert!(matches!(interpreter.eval("てすと＝"), Err(Error::SyntaxError)));
+    }
+
+    fn array_elements(interpreter: &mut Interpreter, expression: &str) -> Vec<Value> {
+        let array = interpreter.eval(expression).unwrap();
+        let len = interpreter.call_method(&array, "要素数", &[]).unwrap().as_num().unwrap();
+        (1..=len as usize)
+            .map(|i| interpreter.call_method(&array, "読む", &[Value::Num(i as f64)]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_reflection() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた：四角＝「｜長さ｜ かめた！（長さ） 歩く。」。
+かめた：歩幅＝１０。
+"#);
+
+        let methods = array_elements(&mut interpreter, "かめた！メソッド一覧");
+        assert!(methods.contains(&Value::Str("四角".to_string())));
+        assert!(methods.contains(&Value::Str("歩く".to_string())));
+        assert!(methods.contains(&Value::Str("作る".to_string())));
+        assert!(!methods.contains(&Value::Str("歩幅".to_string())));
+
+        let variables = array_elements(&mut interpreter, "かめた！変数一覧");
+        assert_eq!(variables, vec!["visible", "歩幅", "direction", "image", "line_color", "line_width", "pen", "scale", "speed", "x", "y"].into_iter()
+            .map(|s| Value::Str(s.to_string()))
+            .collect::<Vec<_>>());
+
+        assert_eq!(interpreter.eval("かめた！親").unwrap(), interpreter.eval("タートル").unwrap());
+        assert_eq!(interpreter.eval("ルート！親").unwrap(), Value::Null);
+        assert_eq!(interpreter.eval("かめた！（タートル） 種類").unwrap(), Value::Bool(true));
+        assert_eq!(interpreter.eval("かめた！（ボタン） 種類").unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_super_call() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"回数＝０。
+かめ型＝タートル！作る。
+かめ型：歩く＝「｜ｎ｜ 回数＝回数＋１。 自分！（ｎ） 親メソッド。」。
+子がめ型＝かめ型！作る。
+子がめ型：歩く＝「｜ｎ｜ 回数＝回数＋１０。 自分！（ｎ＊２） 親メソッド。」。
+かめこ＝子がめ型！作る。
+かめこ！５０　歩く。
+"#);
+
+        assert_eq!(interpreter.eval("回数").unwrap(), Value::Num(11.0));
+        assert_eq!(interpreter.eval("かめこ：x").unwrap(), Value::Num(100.0));
+        assert_eq!(interpreter.eval("かめ型：x").unwrap(), Value::Num(0.0));
+        assert!(matches!(interpreter.try_exec("かめこ！親メソッド。"), Err(Error::Runtime)));
+    }
+
+    #[test]
+    fn test_pen() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた！１００　歩く。
+かめた！ペンなし　１００　歩く。
+かめた！ペンあり　（赤）　線の色　３　線の太さ　１００　歩く。
+"#);
+
+        let vm = interpreter.vm();
+        let line_id = vm.get_object_in_assigns_from_symbol("線").unwrap().id();
+        let mut lines: Vec<_> = interpreter.get_objects().into_iter()
+            .filter(|obj| obj.is_subclass(line_id, vm))
+            .map(|obj| (
+                obj.get_member_str("x1", vm).unwrap().as_num().unwrap(),
+                obj.get_member_str("color", vm).unwrap(),
+                obj.get_member_str("width", vm).unwrap(),
+            ))
+            .collect();
+        lines.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        assert_eq!(lines, vec![
+            (0.0, Value::Str("#000000".to_string()), Value::Num(1.0)),
+            (200.0, Value::Str("#ff0000".to_string()), Value::Num(3.0)),
+        ]);
+        assert!(matches!(interpreter.try_exec("かめた！０　線の太さ。"), Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn test_trails() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた！１０　歩く　９０　右回り　１０　歩く。
+かめた！（赤）　線の色　１０　歩く。
+かめた！ペンなし　１０　歩く　ペンあり　１０　歩く。
+"#);
+
+        let vm = interpreter.vm();
+        let line_id = vm.get_object_in_assigns_from_symbol("線").unwrap().id();
+        let mut lines: Vec<_> = interpreter.get_objects().into_iter()
+            .filter(|obj| obj.is_subclass(line_id, vm))
+            .map(|obj| (
+                core::object::line::points(obj, vm).unwrap().len(),
+                obj.get_member_str("color", vm).unwrap().as_str().unwrap().to_string(),
+            ))
+            .collect();
+        lines.sort();
+        assert_eq!(lines, vec![
+            (2, "#ff0000".to_string()),
+            (2, "#ff0000".to_string()),
+            (3, "#000000".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_trail_after_assigning_position() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec("かめた＝タートル！作る。 かめた！１００　歩く。 かめた：x＝５００。 かめた！１０　歩く。");
+
+        let vm = interpreter.vm();
+        let line_id = interpreter.get_object_id("線");
+        let mut lines: Vec<_> = interpreter.get_objects().into_iter()
+            .filter(|obj| obj.is_subclass(line_id, vm))
+            .filter_map(|obj| core::object::line::points(obj, vm).ok())
+            .collect();
+        lines.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+        assert_eq!(lines, vec![
+            vec![(0.0, 0.0), (100.0, 0.0)],
+            vec![(500.0, 0.0), (510.0, 0.0)],
+        ]);
+    }
+
+    #[test]
+    fn test_animation() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_animated(true);
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた！５０　速さ　１００　歩く　９０　左回り。
+"#);
+
+        let kameta = interpreter.get_object_id("かめた");
+        let line_id = interpreter.get_object_id("線");
+        let vm = interpreter.vm();
+        assert_eq!(vm.get_object(kameta).unwrap().get_member_str("x", vm).unwrap(), Value::Num(100.0));
+        let line = interpreter.get_objects().into_iter().find(|obj| obj.is_subclass(line_id, vm)).unwrap().id();
+
+        interpreter.tick(1.0).unwrap();
+        let vm = interpreter.vm();
+        let points = core::object::line::points(vm.get_object(line).unwrap(), vm).unwrap();
+        assert_eq!(vm.animation().position(kameta, (100.0, 0.0)), (50.0, 0.0));
+        assert_eq!(vm.animation().line_points(line, points), vec![(0.0, 0.0), (50.0, 0.0)]);
+
+        interpreter.tick(1.3).unwrap();
+        assert!(nearly_equal_with_eps(45.0, interpreter.vm().animation().direction(kameta, 90.0), eps));
+
+        interpreter.tick(1.0).unwrap();
+        assert!(!interpreter.vm().animation().is_playing());
+        assert!(matches!(interpreter.try_exec("かめた！０　速さ。"), Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+回った＝０。
+時計＝タイマー！作る　０．５　間隔　４　回数。
+時計！「｜ｎ｜ かめた！１０　歩く。 回った＝ｎ。」　実行。
+秒針＝タイマー！作る　１　間隔。
+秒針！「かめた！９０　左回り。」　実行。
+"#);
+        let kameta = |interpreter: &Interpreter, name: &str| interpreter.vm().get_object_in_assigns_from_symbol("かめた").unwrap()
+            .get_member_str(name, interpreter.vm()).unwrap().as_num().unwrap();
+        assert_eq!(kameta(&interpreter, "x"), 0.0);
+
+        interpreter.tick(0.75).unwrap();
+        assert_eq!(kameta(&interpreter, "x"), 10.0);
+        interpreter.tick(0.25).unwrap();
+        assert_eq!(kameta(&interpreter, "direction"), 90.0);
+        assert_eq!(interpreter.eval("回った。").unwrap(), Value::Num(2.0));
+
+        interpreter.exec("秒針！止める。");
+        interpreter.collect_garbage();
+        interpreter.tick(10.0).unwrap();
+        assert_eq!(interpreter.eval("回った。").unwrap(), Value::Num(4.0));
+        assert_eq!(kameta(&interpreter, "direction"), 90.0);
+        assert_eq!(interpreter.vm().scheduler().now(), 11.0);
+        assert!(matches!(interpreter.try_exec("時計！０　間隔。"), Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn test_collision() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かべ＝タートル！作る　消える　４０　１００　移動する　２７０　向き　２００　歩く。
+かめた＝タートル！作る。
+かめきち＝タートル！作る　２００　０　移動する。
+ぶつかった＝０。
+かめた：衝突＝「｜相手｜ ぶつかった＝ぶつかった＋１。 最後＝相手。」。
+かめきち：衝突＝「｜相手｜ 相手！ペンなし　２０　戻る。」。
+かめた！１０　歩く。
+"#);
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(0.0));
+
+        interpreter.exec("かめた！２０　歩く　５　歩く。");
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(1.0));
+        let line_id = interpreter.get_object_id("線");
+        let last = interpreter.eval("最後。").unwrap();
+        assert!(last.as_object(interpreter.vm()).unwrap().is_subclass(line_id, interpreter.vm()));
+
+        interpreter.exec("かめた！ペンなし　１４５　歩く。");
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(2.0));
+        assert_eq!(interpreter.eval("最後。").unwrap(), interpreter.eval("かめきち。").unwrap());
+        assert_eq!(interpreter.vm().get_object_in_assigns_from_symbol("かめた").unwrap()
+            .get_member_str("x", interpreter.vm()).unwrap(), Value::Num(160.0));
+    }
+
+    #[test]
+    fn test_collision_while_animating() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る　ペンなし。
+かめきち＝タートル！作る　２００　０　移動する。
+ぶつかった＝０。
+かめた：衝突＝「｜相手｜ ぶつかった＝ぶつかった＋１。」。
+"#);
+        interpreter.set_animated(true);
+        interpreter.exec("かめた！２００　歩く。");
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(0.0));
+
+        interpreter.tick(1.0).unwrap();
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(0.0));
+        interpreter.tick(0.9).unwrap();
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(1.0));
+        interpreter.tick(1.0).unwrap();
+        assert_eq!(interpreter.eval("ぶつかった。").unwrap(), Value::Num(1.0));
+    }
+
+    #[test]
+    fn test_turtle_positioning() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめきち＝タートル！作る。
+かめた！３０　４０　移動する。
+長さ＝かめきち！（かめた） 距離。
+かめきち！（かめた） その方向を向く （長さ） 歩く。
+かめた！１８０　向き　５０　戻る。
+"#);
+
+        let kameta = array_elements(&mut interpreter, "かめた！位置");
+        assert!(nearly_equal_with_eps(80.0, kameta[0].as_num().unwrap(), eps));
+        assert!(nearly_equal_with_eps(40.0, kameta[1].as_num().unwrap(), eps));
+        let kamekichi = array_elements(&mut interpreter, "かめきち！位置");
+        assert!(nearly_equal_with_eps(30.0, kamekichi[0].as_num().unwrap(), eps));
+        assert!(nearly_equal_with_eps(40.0, kamekichi[1].as_num().unwrap(), eps));
+        assert!(nearly_equal_with_eps(
+            50.0, interpreter.eval("かめきち！（かめた） 距離").unwrap().as_num().unwrap(), eps));
+
+        interpreter.exec("かめた！中心に戻る。");
+        assert_eq!(array_elements(&mut interpreter, "かめた！位置"), vec![Value::Num(0.0), Value::Num(0.0)]);
+        assert_eq!(interpreter.eval("かめた：direction").unwrap(), Value::Num(0.0));
+    }
+
+    #[test]
+    fn test_make_shape() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+「かめた！１００　歩く　９０　左回り。」！４　繰り返す。
+四角＝かめた！図形にする。
+四角！（赤）　塗る　４５　回転する。
+"#);
+
+        let vm = interpreter.vm();
+        let line_id = vm.get_object_in_assigns_from_symbol("線").unwrap().id();
+        assert!(interpreter.get_objects().into_iter()
+            .filter(|obj| obj.is_subclass(line_id, vm))
+            .all(|obj| obj.get_member_str("visible", vm).unwrap() == Value::Bool(false)));
+
+        let square = vm.get_object_in_assigns_from_symbol("四角").unwrap();
+        assert_eq!(square.get_member_str("fill", vm).unwrap(), Value::Str("#ff0000".to_string()));
+        let points = core::object::shape::points(square, vm).unwrap();
+        assert_eq!(points.len(), 4);
+        let half_diagonal = 50.0 * 2f64.sqrt();
+        assert!(nearly_equal_with_eps(50.0, points[0].0, eps));
+        assert!(nearly_equal_with_eps(50.0 - half_diagonal, points[0].1, eps));
+
+        assert!(matches!(interpreter.try_exec("かめた！図形にする。"), Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn test_curves() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた！５０　円　１００　９０　弧。
+かめた！１００　２００　０　２００　曲線。
+"#);
+
+        let vm = interpreter.vm();
+        let stroke = |name: &str| {
+            let prototype = vm.get_object_in_assigns_from_symbol(name).unwrap().id();
+            let strokes: Vec<_> = interpreter.get_objects().into_iter()
+                .filter(|obj| obj.is_subclass(prototype, vm))
+                .collect();
+            assert_eq!(strokes.len(), 1);
+            strokes[0]
+        };
+        assert_eq!(stroke("円").get_member_str("r", vm).unwrap(), Value::Num(50.0));
+        let arc = stroke("弧");
+        assert_eq!(arc.get_member_str("start", vm).unwrap(), Value::Num(-90.0));
+        assert_eq!(arc.get_member_str("end", vm).unwrap(), Value::Num(0.0));
+        assert!(nearly_equal_with_eps(
+            100.0 + 100.0 * 2.0 / 3.0, stroke("曲線").get_member_str("cy1", vm).unwrap().as_num().unwrap(), eps));
+
+        let kameta = vm.get_object_in_assigns_from_symbol("かめた").unwrap();
+        assert!(nearly_equal_with_eps(0.0, kameta.get_member_str("x", vm).unwrap().as_num().unwrap(), eps));
+        assert!(nearly_equal_with_eps(200.0, kameta.get_member_str("y", vm).unwrap().as_num().unwrap(), eps));
+        assert!(nearly_equal_with_eps(
+            180.0, kameta.get_member_str("direction", vm).unwrap().as_num().unwrap(), eps));
+        assert!(matches!(interpreter.try_exec("かめた！１　２　３　曲線。"), Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn test_colors() {
+        use core::object::color::rgb_of;
+        use utilities::color::Rgb;
+
+        let mut interpreter = Interpreter::new();
+        interpreter.exec(r#"紫＝赤！（青） 色を混ぜる。
+橙＝色！２５５　１６５　０　作る。
+水色＝色！１８０　５０　１００　HSV。
+でたらめ＝色！ランダム。
+かめた＝タートル！作る。
+かめた！（橙） 線の色 １０ 歩く。
+画面！（水色） 背景色。
+"#);
+
+        let mut rgb = |name: &str| rgb_of(&interpreter.eval(name).unwrap(), interpreter.vm()).unwrap();
+        assert_eq!(rgb("紫"), Rgb::new(128, 0, 128));
+        assert_eq!(rgb("橙"), Rgb::new(255, 165, 0));
+        assert_eq!(rgb("水色"), Rgb::new(128, 255, 255));
+        rgb("でたらめ");
+        assert_eq!(interpreter.eval("かめた：line_color").unwrap(), Value::Str("#ffa500".to_string()));
+        assert_eq!(interpreter.eval("画面：background").unwrap(), Value::Str("#80ffff".to_string()));
+        assert_eq!(interpreter.eval("紫！（色） 種類").unwrap(), Value::Bool(true));
+        assert!(matches!(interpreter.try_exec("かめた！１ 線の色。"), Err(Error::ArgumentError)));
+
+        interpreter.exec("何か＝ルート！作る。 何か：明るさ＝１。 赤！（何か） 混ぜる。");
+        assert_eq!(interpreter.eval("赤：明るさ").unwrap(), Value::Num(1.0));
+    }
+
+    #[test]
+    fn test_turtle_appearance() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"かめた＝タートル！作る。
+かめた！消える　"car.png"　変身する　２　大きさ。
+"#);
+        assert_eq!(interpreter.eval("かめた：visible").unwrap(), Value::Bool(false));
+        assert_eq!(interpreter.eval("かめた：image").unwrap(), Value::Str("car.png".to_string()));
+        assert_eq!(interpreter.eval("かめた：scale").unwrap(), Value::Num(2.0));
+
+        interpreter.exec("かめた！現れる。");
+        assert_eq!(interpreter.eval("かめた：visible").unwrap(), Value::Bool(true));
+        assert!(matches!(interpreter.try_exec("かめた！０　大きさ。"), Err(Error::ArgumentError)));
+        assert!(matches!(interpreter.try_exec("かめた！\"\"　変身する。"), Err(Error::ArgumentError)));
+        assert!(matches!(interpreter.try_exec("かめた！\"../secret.png\"　変身する。"), Err(Error::ArgumentError)));
+        assert!(matches!(interpreter.try_exec("かめた！\"/etc/passwd\"　変身する。"), Err(Error::ArgumentError)));
+    }
+
+    #[test]
+    fn test_mixin() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.exec(r#"回数＝０。
+かめた＝タートル！作る。
+かめた！（ボタン） 混ぜる。
+かめた：動作＝「かめた！１００ 歩く。」。
+かめた！クリック。
+数える型＝ルート！作る。
+数える型：数える＝「回数＝回数＋１。」。
+かめた！（数える型） 混ぜる。
+かめた：数える＝「回数＝回数＋１０。 自分！親メソッド。」。
+かめた！数える。
+"#);
+
+        assert_eq!(interpreter.eval("かめた：x").unwrap(), Value::Num(100.0));
+        assert_eq!(interpreter.eval("かめた！（タートル） 種類").unwrap(), Value::Bool(true));
+        assert_eq!(interpreter.eval("かめた！（ボタン） 種類").unwrap(), Value::Bool(true));
+        assert_eq!(interpreter.eval("回数").unwrap(), Value::Num(11.0));
+        assert!(matches!(interpreter.try_exec("タートル！（かめた） 混ぜる。"), Err(Error::ArgumentError)));
+    }
 }
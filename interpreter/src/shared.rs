@@ -0,0 +1,72 @@
+use crate::Interpreter;
+use core::budget::CancelHandle;
+use core::error::Result;
+use std::sync::{Arc, Mutex};
+
+/// Thread-safe handle to an interpreter for front-ends that run programs on
+/// one thread and draw on another. The VM itself is single-threaded and only
+/// ever touched behind this lock.
+#[derive(Clone)]
+pub struct SharedInterpreter {
+    interpreter: Arc<Mutex<Interpreter>>,
+    cancel_handle: CancelHandle,
+}
+
+impl SharedInterpreter {
+    pub fn new(interpreter: Interpreter) -> Self {
+        let cancel_handle = interpreter.cancel_handle();
+        Self {
+            interpreter: Arc::new(Mutex::new(interpreter)),
+            cancel_handle,
+        }
+    }
+
+    pub fn exec(&self, program: &str) -> Result<()> {
+        self.interpreter.lock().unwrap().try_exec(program)
+    }
+
+    // Does not need the lock, so it can stop a program that is holding it.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    pub fn with<R>(&self, f: impl FnOnce(&mut Interpreter) -> R) -> R {
+        f(&mut self.interpreter.lock().unwrap())
+    }
+
+    // Returns `None` while a program is running instead of waiting for it.
+    pub fn try_with<R>(&self, f: impl FnOnce(&mut Interpreter) -> R) -> Option<R> {
+        self.interpreter.try_lock().ok().map(|mut interpreter| f(&mut interpreter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, SharedInterpreter};
+
+    #[test]
+    fn exec_from_another_thread() {
+        let shared = SharedInterpreter::new(Interpreter::new());
+
+        let runner = shared.clone();
+        std::thread::spawn(move || {
+            runner.exec("かめた＝タートル！作る。かめた！１００　歩く。").unwrap();
+        }).join().unwrap();
+
+        let x = shared.with(|interpreter| {
+            let vm = interpreter.vm();
+            vm.get_object_in_assigns_from_symbol("かめた").unwrap()
+                .get_member_str("x", vm).unwrap().as_num().unwrap()
+        });
+        assert_eq!(x, 100.0);
+    }
+
+    #[test]
+    fn try_with_does_not_wait_for_running_program() {
+        let shared = SharedInterpreter::new(Interpreter::new());
+
+        let inner = shared.with(|_| shared.try_with(|_| ()));
+        assert!(inner.is_none());
+        assert!(shared.try_with(|_| ()).is_some());
+    }
+}
@@ -26,6 +26,7 @@ fn to_ascii_digit(input: &str) -> String {
             '７' => '7',
             '８' => '8',
             '９' => '9',
+            '．' => '.',
             _ => c,
         }
     }).collect()
@@ -53,6 +54,7 @@ mod tests {
     #[rstest(input, expected,
     case("１.２３", Ok(1.23f64)),
     case("1.23", Ok(1.23f64)),
+    case("１．２３", Ok(1.23f64)),
     )]
     fn test_parse_unicode_f64(input: &str, expected: Result<f64, ParseFloatError>) {
         assert_eq!(input.parse_unicode(), expected);
@@ -0,0 +1,85 @@
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    // Accepts the `#rrggbb` form used on 線 objects and by canvas.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#')?;
+        if digits.len() != 6 || !digits.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        Some(Self::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    // `h` in degrees, `s` and `v` from 0 to 1.
+    pub fn from_hsv(h: f64, s: f64, v: f64) -> Self {
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let h = h.rem_euclid(360.0) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let channel = |f: f64| ((f + v - c) * 255.0).round() as u8;
+        Self::new(channel(r), channel(g), channel(b))
+    }
+
+    // The average of the colours, like mixing equal amounts of paint.
+    pub fn mix(colors: &[Rgb]) -> Option<Self> {
+        if colors.is_empty() {
+            return None;
+        }
+        let n = colors.len() as f64;
+        let average = |f: fn(&Rgb) -> u8| (colors.iter().map(|c| f(c) as f64).sum::<f64>() / n).round() as u8;
+        Some(Self::new(average(|c| c.r), average(|c| c.g), average(|c| c.b)))
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    pub fn to_rgba(&self) -> [f32; 4] {
+        [self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0, 1.0]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Rgb;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(Rgb::from_hex("#ff8000"), Some(Rgb::new(255, 128, 0)));
+        assert_eq!(Rgb::new(255, 128, 0).to_hex(), "#ff8000");
+        assert_eq!(Rgb::from_hex("ff8000"), None);
+        assert_eq!(Rgb::from_hex("#ff80"), None);
+    }
+
+    #[test]
+    fn test_hsv_and_mix() {
+        assert_eq!(Rgb::from_hsv(0.0, 1.0, 1.0), Rgb::new(255, 0, 0));
+        assert_eq!(Rgb::from_hsv(120.0, 1.0, 1.0), Rgb::new(0, 255, 0));
+        assert_eq!(Rgb::from_hsv(240.0, 0.5, 1.0), Rgb::new(128, 128, 255));
+        assert_eq!(Rgb::from_hsv(360.0, 0.0, 0.0), Rgb::BLACK);
+
+        assert_eq!(Rgb::mix(&[Rgb::new(255, 0, 0), Rgb::new(0, 0, 255)]), Some(Rgb::new(128, 0, 128)));
+        assert_eq!(Rgb::mix(&[]), None);
+    }
+}
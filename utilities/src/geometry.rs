@@ -1,6 +1,7 @@
 
 pub type Float = f64;
 
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Vector2D {
     pub x: Float,
     pub y: Float,
@@ -15,10 +16,173 @@ pub fn dir_vector(direction_deg: Float) -> Vector2D {
     }
 }
 
+// Turns `v` counterclockwise about the origin, like 左回り.
+pub fn rotate(v: &Vector2D, angle_deg: Float) -> Vector2D {
+    let d = dir_vector(angle_deg);
+    Vector2D {
+        x: v.x * d.x - v.y * d.y,
+        y: v.x * d.y + v.y * d.x,
+    }
+}
+
+pub fn centroid(points: &[Vector2D]) -> Vector2D {
+    let n = points.len().max(1) as Float;
+    Vector2D {
+        x: points.iter().map(|p| p.x).sum::<Float>() / n,
+        y: points.iter().map(|p| p.y).sum::<Float>() / n,
+    }
+}
+
+pub fn point_on_circle(center: &Vector2D, radius: Float, angle_deg: Float) -> Vector2D {
+    let d = dir_vector(angle_deg);
+    Vector2D {
+        x: center.x + radius * d.x,
+        y: center.y + radius * d.y,
+    }
+}
+
+pub fn cubic_bezier(p: &[Vector2D; 4], t: Float) -> Vector2D {
+    let s = 1.0 - t;
+    let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+    Vector2D {
+        x: a * p[0].x + b * p[1].x + c * p[2].x + d * p[3].x,
+        y: a * p[0].y + b * p[1].y + c * p[2].y + d * p[3].y,
+    }
+}
+
+// The control points of the cubic curve that traces the same path as a
+// quadratic one through `control`.
+pub fn quadratic_to_cubic(start: &Vector2D, control: &Vector2D, end: &Vector2D) -> [Vector2D; 4] {
+    let toward = |from: &Vector2D| Vector2D {
+        x: from.x + 2.0 / 3.0 * (control.x - from.x),
+        y: from.y + 2.0 / 3.0 * (control.y - from.y),
+    };
+    [*start, toward(start), toward(end), *end]
+}
+
+pub fn distance(from: &Vector2D, to: &Vector2D) -> Float {
+    (to.x - from.x).hypot(to.y - from.y)
+}
+
+// The heading in degrees that `dir_vector` turns back into the direction
+// from `from` to `to`.
+pub fn direction_to(from: &Vector2D, to: &Vector2D) -> Float {
+    (to.y - from.y).atan2(to.x - from.x).to_degrees()
+}
+
+// How far `p` is from the segment between `a` and `b`.
+pub fn segment_distance(p: &Vector2D, a: &Vector2D, b: &Vector2D) -> Float {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length2 = dx * dx + dy * dy;
+    if length2 == 0.0 {
+        return distance(p, a);
+    }
+    let t = (((p.x - a.x) * dx + (p.y - a.y) * dy) / length2).clamp(0.0, 1.0);
+    distance(p, &Vector2D { x: a.x + t * dx, y: a.y + t * dy })
+}
+
+fn cross(o: &Vector2D, a: &Vector2D, b: &Vector2D) -> Float {
+    (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+}
+
+// Touching ends and overlapping collinear segments count as crossing.
+pub fn segments_intersect(a: &Vector2D, b: &Vector2D, c: &Vector2D, d: &Vector2D) -> bool {
+    let opposite = |s: Float, t: Float| (s > 0.0 && t < 0.0) || (s < 0.0 && t > 0.0);
+    if opposite(cross(c, d, a), cross(c, d, b)) && opposite(cross(a, b, c), cross(a, b, d)) {
+        return true;
+    }
+    const EPS: Float = 1e-9;
+    segment_distance(a, c, d) < EPS || segment_distance(b, c, d) < EPS
+        || segment_distance(c, a, b) < EPS || segment_distance(d, a, b) < EPS
+}
+
+// The outline of something on screen, for finding out what touches what.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Bounds {
+    Circle { center: Vector2D, radius: Float },
+    // open, like a line the turtle drew
+    Polyline(Vec<Vector2D>),
+    // closed, and everything inside counts
+    Polygon(Vec<Vector2D>),
+}
+
+impl Bounds {
+    fn edges(&self) -> impl Iterator<Item = (Vector2D, Vector2D)> + '_ {
+        let (points, closing) = match self {
+            Bounds::Circle { .. } => (&[][..], None),
+            Bounds::Polyline(points) => (&points[..], None),
+            Bounds::Polygon(points) => (&points[..], points.last().copied().zip(points.first().copied())),
+        };
+        points.windows(2).map(|w| (w[0], w[1])).chain(closing)
+    }
+
+    fn contains(&self, p: &Vector2D) -> bool {
+        match self {
+            Bounds::Circle { center, radius } => distance(center, p) <= *radius,
+            Bounds::Polyline(_) => false,
+            // even-odd rule
+            Bounds::Polygon(points) => points.len() > 2 && self.edges()
+                .filter(|(a, b)| (a.y > p.y) != (b.y > p.y)
+                    && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x))
+                .count() % 2 == 1,
+        }
+    }
+
+    fn any_point(&self) -> Option<Vector2D> {
+        match self {
+            Bounds::Circle { center, .. } => Some(*center),
+            Bounds::Polyline(points) | Bounds::Polygon(points) => points.first().copied(),
+        }
+    }
+
+    // The smallest and largest corners of an axis-aligned box around it.
+    fn extent(&self) -> Option<(Vector2D, Vector2D)> {
+        let points = match self {
+            Bounds::Circle { center, radius } => return Some((
+                Vector2D { x: center.x - radius, y: center.y - radius },
+                Vector2D { x: center.x + radius, y: center.y + radius },
+            )),
+            Bounds::Polyline(points) | Bounds::Polygon(points) => points,
+        };
+        let first = *points.first()?;
+        Some(points.iter().fold((first, first), |(min, max), p| (
+            Vector2D { x: min.x.min(p.x), y: min.y.min(p.y) },
+            Vector2D { x: max.x.max(p.x), y: max.y.max(p.y) },
+        )))
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        match (self.extent(), other.extent()) {
+            (Some((min1, max1)), Some((min2, max2)))
+                if min1.x <= max2.x && min2.x <= max1.x && min1.y <= max2.y && min2.y <= max1.y => {}
+            _ => return false,
+        }
+        match (self, other) {
+            (Bounds::Circle { center: c1, radius: r1 }, Bounds::Circle { center: c2, radius: r2 }) =>
+                distance(c1, c2) <= r1 + r2,
+            (Bounds::Circle { center, radius }, shape) | (shape, Bounds::Circle { center, radius }) =>
+                shape.contains(center) || shape.edges().any(|(a, b)| {
+                    // most edges of a long trail are far away; skip them before measuring
+                    let near = |p: Float, q: Float, c: Float| p.min(q) - radius <= c && c <= p.max(q) + radius;
+                    near(a.x, b.x, center.x) && near(a.y, b.y, center.y)
+                        && segment_distance(center, &a, &b) <= *radius
+                }),
+            _ => {
+                let theirs: Vec<_> = other.edges().collect();
+                self.edges().any(|(a, b)| theirs.iter().any(|(c, d)| segments_intersect(&a, &b, c, d)))
+                    || self.any_point().is_some_and(|p| other.contains(&p))
+                    || other.any_point().is_some_and(|p| self.contains(&p))
+            }
+        }
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
-    use crate::geometry::{dir_vector, Float};
+    use crate::geometry::{centroid, cubic_bezier, dir_vector, distance, direction_to, point_on_circle,
+                          quadratic_to_cubic, rotate, segment_distance, segments_intersect, Bounds, Float,
+                          Vector2D};
 
     const EPS: Float = 1e-5;
 
@@ -27,4 +191,69 @@ mod tests {
         let v = dir_vector(45.0f64);
         assert!((v.x - v.y).abs() < EPS);
     }
+
+    #[test]
+    fn test_distance_and_direction() {
+        let from = Vector2D { x: 1.0, y: 1.0 };
+        let to = Vector2D { x: 4.0, y: 5.0 };
+        assert!((distance(&from, &to) - 5.0).abs() < EPS);
+
+        let v = dir_vector(direction_to(&from, &to));
+        assert!((v.x - 0.6).abs() < EPS);
+        assert!((v.y - 0.8).abs() < EPS);
+    }
+
+    #[test]
+    fn test_rotate_and_centroid() {
+        let v = rotate(&Vector2D { x: 1.0, y: 0.0 }, 90.0);
+        assert!(v.x.abs() < EPS);
+        assert!((v.y - 1.0).abs() < EPS);
+
+        let c = centroid(&[
+            Vector2D { x: 0.0, y: 0.0 },
+            Vector2D { x: 2.0, y: 0.0 },
+            Vector2D { x: 2.0, y: 2.0 },
+            Vector2D { x: 0.0, y: 2.0 },
+        ]);
+        assert_eq!(c, Vector2D { x: 1.0, y: 1.0 });
+    }
+
+    #[test]
+    fn test_curves() {
+        let p = point_on_circle(&Vector2D { x: 1.0, y: 1.0 }, 2.0, 90.0);
+        assert!((p.x - 1.0).abs() < EPS);
+        assert!((p.y - 3.0).abs() < EPS);
+
+        let start = Vector2D { x: 0.0, y: 0.0 };
+        let control = Vector2D { x: 1.0, y: 2.0 };
+        let end = Vector2D { x: 2.0, y: 0.0 };
+        let cubic = quadratic_to_cubic(&start, &control, &end);
+        assert_eq!(cubic_bezier(&cubic, 0.0), start);
+        let middle = cubic_bezier(&cubic, 0.5);
+        assert!((middle.x - 1.0).abs() < EPS);
+        assert!((middle.y - 1.0).abs() < EPS);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let v = |x: Float, y: Float| Vector2D { x, y };
+        assert!((segment_distance(&v(1.0, 1.0), &v(0.0, 0.0), &v(2.0, 0.0)) - 1.0).abs() < EPS);
+        assert!(segments_intersect(&v(0.0, 0.0), &v(2.0, 2.0), &v(0.0, 2.0), &v(2.0, 0.0)));
+        assert!(!segments_intersect(&v(0.0, 0.0), &v(1.0, 0.0), &v(0.0, 1.0), &v(1.0, 1.0)));
+
+        let square = Bounds::Polygon(vec![v(0.0, 0.0), v(10.0, 0.0), v(10.0, 10.0), v(0.0, 10.0)]);
+        let inside = Bounds::Circle { center: v(5.0, 5.0), radius: 1.0 };
+        let near = Bounds::Circle { center: v(12.0, 5.0), radius: 2.0 };
+        let far = Bounds::Circle { center: v(13.0, 5.0), radius: 2.0 };
+        assert!(square.intersects(&inside));
+        assert!(near.intersects(&square));
+        assert!(!far.intersects(&square));
+        assert!(far.intersects(&near));
+
+        let crossing = Bounds::Polyline(vec![v(-5.0, 5.0), v(5.0, 5.0)]);
+        let outside = Bounds::Polyline(vec![v(-5.0, 20.0), v(5.0, 20.0), v(5.0, 15.0)]);
+        assert!(crossing.intersects(&square));
+        assert!(!outside.intersects(&square));
+        assert!(!outside.intersects(&crossing));
+    }
 }
@@ -1,3 +1,4 @@
+pub mod color;
 pub mod geometry;
 pub mod test_helper;
 
@@ -15,19 +15,61 @@ const f = async (visualObjects) => {
     ctx.translate(canvas.width / 2, canvas.height / 2);
 
     for (let p of visualObjects.map(async vo => {
-       if (vo.type === "ImageObject") {
+       if (vo.type === "Background") {
+           ctx.fillStyle = vo.content.color;
+           ctx.fillRect(-canvas.width / 2, -canvas.height / 2, canvas.width, canvas.height);
+       }
+       else if (vo.type === "ImageObject") {
            ctx.save();
            ctx.translate(vo.content.x, vo.content.y);
            console.log(vo.content.rotation);
            ctx.rotate(-vo.content.rotation);
+           ctx.scale(vo.content.scale, vo.content.scale);
            console.log(vo.content.image);
            let image = await loadImage("assets/" + vo.content.image);
            ctx.drawImage(image, -image.width / 2, -image.height / 2);
            ctx.restore();
        }
        else if (vo.type === "Line") {
-           ctx.moveTo(vo.content.x1, vo.content.y1);
-           ctx.lineTo(vo.content.x2, vo.content.y2);
+           ctx.beginPath();
+           ctx.strokeStyle = vo.content.color;
+           ctx.lineWidth = vo.content.width;
+           // one sub-path per step, so corners look as they did with one line each
+           vo.content.points.slice(1).forEach(([x, y], i) => {
+               const [px, py] = vo.content.points[i];
+               ctx.moveTo(px, py);
+               ctx.lineTo(x, y);
+           });
+           ctx.stroke();
+       }
+       else if (vo.type === "Circle" || vo.type === "Arc" || vo.type === "Bezier") {
+           ctx.beginPath();
+           ctx.strokeStyle = vo.content.color;
+           ctx.lineWidth = vo.content.width;
+           if (vo.type === "Circle") {
+               ctx.arc(vo.content.x, vo.content.y, vo.content.radius, 0, 2 * Math.PI);
+           }
+           else if (vo.type === "Arc") {
+               ctx.arc(vo.content.x, vo.content.y, vo.content.radius,
+                   vo.content.start, vo.content.end, vo.content.end < vo.content.start);
+           }
+           else {
+               const [p0, p1, p2, p3] = vo.content.points;
+               ctx.moveTo(p0[0], p0[1]);
+               ctx.bezierCurveTo(p1[0], p1[1], p2[0], p2[1], p3[0], p3[1]);
+           }
+           ctx.stroke();
+       }
+       else if (vo.type === "Polygon") {
+           ctx.beginPath();
+           vo.content.points.forEach(([x, y]) => ctx.lineTo(x, y));
+           ctx.closePath();
+           if (vo.content.fill !== null) {
+               ctx.fillStyle = vo.content.fill;
+               ctx.fill();
+           }
+           ctx.strokeStyle = vo.content.color;
+           ctx.lineWidth = vo.content.width;
            ctx.stroke();
        }
     })) {
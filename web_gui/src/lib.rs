@@ -5,13 +5,13 @@ mod viewmodel;
 
 use std::f64;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::__rt::std::sync::{Arc, RwLock};
 use crate::viewmodel::{InterpreterViewModel, ViewModel};
 use wasm_bindgen::JsCast;
 use web_sys;
 use js_sys;
 use wasm_bindgen_futures::JsFuture;
-use interpreter::Interpreter;
+use interpreter::{Interpreter, SharedInterpreter};
+use core::budget::CancelHandle;
 
 #[wasm_bindgen(inline_js = "module.exports.sleep = function sleep(ms) { return new Promise((resolve)=> setTimeout(resolve, ms)); }")]
 extern "C"  {
@@ -23,8 +23,17 @@ extern "C" {
     fn alert(s: &str);
 }
 
+const STEP_LIMIT: u64 = 10_000_000;
+const FRAME_MS: f64 = 1000.0 / 30.0;
+
 lazy_static! {
-    pub static ref interp: Arc<RwLock<Interpreter>> = Arc::new(RwLock::new(Interpreter::new()));
+    pub static ref interp: SharedInterpreter = {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_step_limit(Some(STEP_LIMIT));
+        interpreter.set_animated(true);
+        SharedInterpreter::new(interpreter)
+    };
+    static ref cancel_handle: CancelHandle = interp.cancel_handle();
 }
 
 #[wasm_bindgen]
@@ -34,15 +43,24 @@ pub async fn run(f: JsValue) {
     let f = js_sys::Function::from(f);
 
     loop {
+        if let Some(Err(e)) = interp.try_with(|interpreter| interpreter.tick(FRAME_MS / 1000.0)) {
+            alert(&format!("{:?}", e));
+        }
         let visual_objects =
             JsValue::from_serde(&view_model.visual_objects()).unwrap();
         f.call1(&JsValue::NULL, &visual_objects).unwrap();
-        JsFuture::from(sleep(1000.0)).await.unwrap();
+        JsFuture::from(sleep(FRAME_MS)).await.unwrap();
     }
 }
 
 
 #[wasm_bindgen]
-pub fn exec(code: &str) {
-    interp.write().unwrap().exec(code);
+pub fn exec(code: &str) -> Result<(), JsValue> {
+    interp.exec(code)
+        .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+}
+
+#[wasm_bindgen]
+pub fn cancel() {
+    cancel_handle.cancel();
 }
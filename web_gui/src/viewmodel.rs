@@ -1,7 +1,5 @@
-use std::path::PathBuf;
-use interpreter::Interpreter;
+use interpreter::{Interpreter, SharedInterpreter};
 use std::rc::Rc;
-use std::sync::{Arc, RwLock};
 use core::object::Object;
 use std::cell::RefCell;
 use serde::Serialize;
@@ -13,6 +11,11 @@ use core::types::Value;
 pub enum VisualObject {
     ImageObject(ImageObjectImpl),
     Line(LineImpl),
+    Polygon(PolygonImpl),
+    Circle(CircleImpl),
+    Arc(ArcImpl),
+    Bezier(BezierImpl),
+    Background(BackgroundImpl),
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -20,15 +23,56 @@ pub struct ImageObjectImpl {
     pub x: f64,
     pub y: f64,
     pub rotation: f64,
-    pub image: PathBuf,
+    pub image: String,
+    pub scale: f64,
 }
 
 #[derive(Clone, Debug, Serialize)]
 pub struct LineImpl {
-    pub x1: f64,
-    pub y1: f64,
-    pub x2: f64,
-    pub y2: f64,
+    pub points: Vec<(f64, f64)>,
+    pub color: String,
+    pub width: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PolygonImpl {
+    pub points: Vec<(f64, f64)>,
+    pub color: String,
+    pub width: f64,
+    pub fill: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CircleImpl {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    pub color: String,
+    pub width: f64,
+}
+
+// start and end are in radians
+#[derive(Clone, Debug, Serialize)]
+pub struct ArcImpl {
+    pub x: f64,
+    pub y: f64,
+    pub radius: f64,
+    pub start: f64,
+    pub end: f64,
+    pub color: String,
+    pub width: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BezierImpl {
+    pub points: Vec<(f64, f64)>,
+    pub color: String,
+    pub width: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BackgroundImpl {
+    pub color: String,
 }
 
 pub trait ViewModel {
@@ -36,50 +80,122 @@ pub trait ViewModel {
 }
 
 pub struct InterpreterViewModel {
-    model: Arc<RwLock<Interpreter>>,
+    model: SharedInterpreter,
 }
 
 impl ViewModel for InterpreterViewModel {
     fn visual_objects(&self) -> Vec<VisualObject> {
-        let model = self.model.read().unwrap();
-        let x = model.get_symbol("x");
-        let y = model.get_symbol("y");
-        let x1 = model.get_symbol("x1");
-        let y1= model.get_symbol("y1");
-        let x2 = model.get_symbol("x2");
-        let y2= model.get_symbol("y2");
-        let direction = model.get_symbol("direction");
-        let visible = model.get_symbol("visible");
-        let turtle_obj_id = model.get_object_id("タートル");
-        let line_obj_id = model.get_object_id("線");
-        let mut visualObjects: Vec<VisualObject> = model.get_objects()
-            .iter()
-            .filter(|obj| obj.is_subclass(turtle_obj_id) && obj.get_member(visible).unwrap_or(Value::Bool(false)).as_bool().unwrap())
-            .map(|obj| {
-                VisualObject::ImageObject(ImageObjectImpl {
-                    x: obj.get_member(x).unwrap().as_num().unwrap(),
-                    y: obj.get_member(y).unwrap().as_num().unwrap(),
-                    rotation: obj.get_member(direction).unwrap().as_num().unwrap().to_radians(),
-                    image: PathBuf::from("ayumi.png"),
-                })
-            }).collect();
-        visualObjects.extend(model.get_objects()
-            .iter().filter(|obj| obj.is_subclass(line_obj_id))
-            .map(|obj| {
-                VisualObject::Line(LineImpl {
-                    x1: obj.get_member(x1).unwrap().as_num().unwrap(),
-                    y1: obj.get_member(y1).unwrap().as_num().unwrap(),
-                    x2: obj.get_member(x2).unwrap().as_num().unwrap(),
-                    y2: obj.get_member(y2).unwrap().as_num().unwrap(),
-                })
-        }));
-        visualObjects.reverse();
-        visualObjects
+        self.model.with(|model| visual_objects_of(model))
     }
 }
 
+fn visual_objects_of(model: &Interpreter) -> Vec<VisualObject> {
+    let vm = model.vm();
+    let x = model.get_symbol("x");
+    let y = model.get_symbol("y");
+    let color = model.get_symbol("color");
+    let width = model.get_symbol("width");
+    let fill = model.get_symbol("fill");
+    let direction = model.get_symbol("direction");
+    let visible = model.get_symbol("visible");
+    let image = model.get_symbol("image");
+    let scale = model.get_symbol("scale");
+    let turtle_obj_id = model.get_object_id("タートル");
+    let line_obj_id = model.get_object_id("線");
+    let shape_obj_id = model.get_object_id("図形");
+    let circle_obj_id = model.get_object_id("円");
+    let arc_obj_id = model.get_object_id("弧");
+    let curve_obj_id = model.get_object_id("曲線");
+    let num = |obj: &Object, name: &str| obj.get_member_str(name, vm).unwrap().as_num().unwrap();
+    let stroke_color = |obj: &Object| obj.get_member(color, vm).unwrap().as_str().unwrap().to_string();
+    let animation = vm.animation();
+    let mut visualObjects: Vec<VisualObject> = model.get_objects()
+        .iter()
+        .filter(|obj| obj.is_subclass(turtle_obj_id, vm) && obj.get_member(visible, vm).unwrap_or(Value::Bool(false)).as_bool().unwrap())
+        .map(|obj| {
+            let (x, y) = animation.position(obj.id(), (
+                obj.get_member(x, vm).unwrap().as_num().unwrap(),
+                obj.get_member(y, vm).unwrap().as_num().unwrap(),
+            ));
+            VisualObject::ImageObject(ImageObjectImpl {
+                x,
+                y,
+                rotation: animation.direction(obj.id(), obj.get_member(direction, vm).unwrap().as_num().unwrap()).to_radians(),
+                image: obj.get_member(image, vm).unwrap().as_str().unwrap().to_string(),
+                scale: obj.get_member(scale, vm).unwrap().as_num().unwrap(),
+            })
+        }).collect();
+    visualObjects.extend(model.get_objects()
+        .iter().filter(|obj| obj.is_subclass(line_obj_id, vm) && obj.get_member(visible, vm).unwrap().as_bool().unwrap())
+        .map(|obj| (obj, animation.line_points(obj.id(), core::object::line::points(obj, vm).unwrap())))
+        .filter(|(_, points)| points.len() > 1)
+        .map(|(obj, points)| {
+            VisualObject::Line(LineImpl {
+                points,
+                color: obj.get_member(color, vm).unwrap().as_str().unwrap().to_string(),
+                width: obj.get_member(width, vm).unwrap().as_num().unwrap(),
+            })
+    }));
+    visualObjects.extend(model.get_objects()
+        .iter().filter(|obj| obj.is_subclass(shape_obj_id, vm) && animation.is_drawn(obj.id()))
+        .filter_map(|obj| {
+            Some(VisualObject::Polygon(PolygonImpl {
+                points: core::object::shape::points(obj, vm).ok()?,
+                color: obj.get_member(color, vm).unwrap().as_str().unwrap().to_string(),
+                width: obj.get_member(width, vm).unwrap().as_num().unwrap(),
+                fill: obj.get_member(fill, vm).unwrap().as_str().ok().map(|s| s.to_string()),
+            }))
+    }));
+    visualObjects.extend(model.get_objects()
+        .iter().filter(|obj| obj.is_subclass(circle_obj_id, vm) && animation.is_drawn(obj.id()))
+        .map(|obj| {
+            VisualObject::Circle(CircleImpl {
+                x: num(obj, "cx"),
+                y: num(obj, "cy"),
+                radius: num(obj, "r"),
+                color: stroke_color(obj),
+                width: num(obj, "width"),
+            })
+    }));
+    visualObjects.extend(model.get_objects()
+        .iter().filter(|obj| obj.is_subclass(arc_obj_id, vm) && animation.is_drawn(obj.id()))
+        .map(|obj| {
+            VisualObject::Arc(ArcImpl {
+                x: num(obj, "cx"),
+                y: num(obj, "cy"),
+                radius: num(obj, "r"),
+                start: num(obj, "start").to_radians(),
+                end: num(obj, "end").to_radians(),
+                color: stroke_color(obj),
+                width: num(obj, "width"),
+            })
+    }));
+    visualObjects.extend(model.get_objects()
+        .iter().filter(|obj| obj.is_subclass(curve_obj_id, vm) && animation.is_drawn(obj.id()))
+        .map(|obj| {
+            VisualObject::Bezier(BezierImpl {
+                points: vec![
+                    (num(obj, "x1"), num(obj, "y1")),
+                    (num(obj, "cx1"), num(obj, "cy1")),
+                    (num(obj, "cx2"), num(obj, "cy2")),
+                    (num(obj, "x2"), num(obj, "y2")),
+                ],
+                color: stroke_color(obj),
+                width: num(obj, "width"),
+            })
+    }));
+    // reversed below, so the background is drawn first
+    visualObjects.extend(vm.get_object_in_assigns_from_symbol("画面").ok().map(|screen| {
+        VisualObject::Background(BackgroundImpl {
+            color: screen.get_member_str("background", vm).unwrap().as_str().unwrap().to_string(),
+        })
+    }));
+    visualObjects.reverse();
+    visualObjects
+}
+
 impl InterpreterViewModel {
-    pub fn new(interpreter: Arc<RwLock<Interpreter>>) -> Self {
+    pub fn new(interpreter: SharedInterpreter) -> Self {
         Self {
             model: interpreter,
         }